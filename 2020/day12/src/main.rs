@@ -23,6 +23,17 @@ F11";
         assert_eq!(movements[4], Movement::Forward(11));
     }
 
+    #[test]
+    fn parse_rejects_partial_turns_test() {
+        assert!(parse_line("R45").is_err());
+        assert!(parse_line("L100").is_err());
+        assert!(parse_line("X10").is_err());
+        assert_eq!(
+            parse_line("L270").unwrap().1,
+            Movement::Turn(TurnDirection::Left, 3)
+        );
+    }
+
     #[test]
     fn calculate_manhattan_distance_test() {
         let (_, movements) = parse_lines(TEST_STR).unwrap();
@@ -33,10 +44,10 @@ F11";
             position_y: 0,
         };
         for m in movements {
-            ship = ship.action_movement(m);
+            ship = ship.action_movement(m).unwrap();
         }
         let md = ship.get_manhattan_distance(0, 0);
-        assert_eq!(25, md);
+        assert_eq!(Some(25), md);
     }
 
     #[test]
@@ -50,10 +61,10 @@ F11";
             wposition_y: 1,
         };
         for m in movements {
-            ship = ship.action_movement(m);
+            ship = ship.action_movement(m).unwrap();
         }
         let md = ship.get_manhattan_distance(0, 0);
-        assert_eq!(286, md);
+        assert_eq!(Some(286), md);
     }
 
     #[test]
@@ -65,31 +76,242 @@ F11";
             wposition_x: 10,
             wposition_y: 1,
         };
-        ship = ship.action_movement(movements[0]);
+        ship = ship.action_movement(movements[0]).unwrap();
         assert_eq!(100, ship.x);
         assert_eq!(10, ship.y);
-        ship = ship.action_movement(movements[1]);
+        ship = ship.action_movement(movements[1]).unwrap();
         assert_eq!(100, ship.x);
         assert_eq!(10, ship.y);
-        ship = ship.action_movement(movements[2]);
+        ship = ship.action_movement(movements[2]).unwrap();
         assert_eq!(170, ship.x);
         assert_eq!(38, ship.y);
-        ship = ship.action_movement(movements[3]);
+        ship = ship.action_movement(movements[3]).unwrap();
         assert_eq!(170, ship.x);
         assert_eq!(38, ship.y);
-        ship = ship.action_movement(movements[4]);
+        ship = ship.action_movement(movements[4]).unwrap();
         assert_eq!(4, ship.wposition_x);
         assert_eq!(-10, ship.wposition_y);
         assert_eq!(214, ship.x);
         assert_eq!(-72, ship.y);
     }
+
+    #[test]
+    fn trajectory_test() {
+        let (_, movements) = parse_lines(TEST_STR).unwrap();
+        let ship = Ship {
+            direction: Direction::East,
+            position_x: 0,
+            position_y: 0,
+        };
+        let trajectory = Trajectory::navigate(ship, movements).unwrap();
+        assert_eq!(6, trajectory.states.len());
+        assert_eq!(Some(25), trajectory.states[5].get_manhattan_distance(0, 0));
+
+        let csv = trajectory.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!("step,x,y,heading_x,heading_y", lines[0]);
+        assert_eq!("0,0,0,1,0", lines[1]);
+        assert_eq!("4,17,3,0,-1", lines[5]);
+        assert_eq!("5,17,-8,0,-1", lines[6]);
+
+        let svg = trajectory.to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("points=\"0,0 10,0 10,-3 17,-3 17,-3 17,8\""));
+    }
+
+    #[test]
+    fn trajectory_with_waypoint_test() {
+        let (_, movements) = parse_lines(TEST_STR).unwrap();
+        let ship = ShipWithWayPoint {
+            x: 0,
+            y: 0,
+            wposition_x: 10,
+            wposition_y: 1,
+        };
+        let trajectory = Trajectory::navigate(ship, movements).unwrap();
+        let csv = trajectory.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!("2,100,10,10,4", lines[3]);
+        assert_eq!("5,214,-72,4,-10", lines[6]);
+    }
+
+    #[test]
+    fn forward_in_one_step_test() {
+        let ship = ShipWithWayPoint {
+            x: 0,
+            y: 0,
+            wposition_x: 10,
+            wposition_y: -1,
+        };
+        let ship = ship
+            .action_movement(Movement::Forward(1_000_000_000_000))
+            .unwrap();
+        assert_eq!(10_000_000_000_000, ship.x);
+        assert_eq!(-1_000_000_000_000, ship.y);
+        assert_eq!(None, ship.forward(i64::MAX));
+    }
+
+    #[test]
+    fn fold_movements_test() {
+        let ship = ShipWithWayPoint {
+            x: 0,
+            y: 0,
+            wposition_x: 10,
+            wposition_y: 1,
+        };
+        let (_, movements) = parse_lines(TEST_STR).unwrap();
+        let route = movements.clone().into_iter().cycle().take(5_000_000);
+        assert!(fold_movements(ship, route).is_ok());
+        let ship = fold_movements(ship, movements).unwrap();
+        assert_eq!((214, -72), ship.position());
+
+        let movements = [Movement::Forward(i64::MAX), Movement::Forward(i64::MAX)];
+        let ship = Ship {
+            direction: Direction::East,
+            position_x: 0,
+            position_y: 0,
+        };
+        assert_eq!(
+            Err(NavigationError::Overflow { step: 2 }),
+            fold_movements(ship, movements)
+        );
+    }
+
+    #[test]
+    fn fold_reader_test() {
+        let ship = Ship {
+            direction: Direction::East,
+            position_x: 0,
+            position_y: 0,
+        };
+        let ship = fold_reader(ship, TEST_STR.as_bytes()).unwrap();
+        assert_eq!(Some(25), ship.get_manhattan_distance(0, 0));
+
+        let input = "F10\nR45\nF1";
+        assert_eq!(
+            Err(NavigationError::Parse { line: 2 }),
+            fold_reader(ship, input.as_bytes())
+        );
+
+        // Overflow is reported by line too, blank lines included.
+        let input = format!("S1\n\nS{}", i64::MAX);
+        assert_eq!(
+            Err(NavigationError::Overflow { step: 3 }),
+            fold_reader(ship, input.as_bytes())
+        );
+    }
+
+    #[test]
+    fn manhattan_distance_overflow_test() {
+        let ship = Ship {
+            direction: Direction::East,
+            position_x: i64::MIN,
+            position_y: 0,
+        };
+        assert_eq!(Some(u64::MAX), ship.get_manhattan_distance(i64::MAX, 0));
+        assert_eq!(None, ship.get_manhattan_distance(i64::MAX, 1));
+        assert_eq!(Some(i64::MAX as u64 + 1), ship.get_manhattan_distance(0, 0));
+    }
+
+    #[test]
+    fn plan_route_test() {
+        let ship = Ship {
+            direction: Direction::East,
+            position_x: 0,
+            position_y: 0,
+        };
+        let route = plan_route(ship, (17, -8), PlanConstraints::ANY).unwrap();
+        assert_eq!(2, route.len());
+        assert!(validate_route(ship, &route, (17, -8)));
+
+        let route = plan_route(ship, (17, -8), PlanConstraints::FORWARD_AND_TURNS).unwrap();
+        assert_eq!(
+            vec![
+                Movement::Forward(17),
+                Movement::Turn(TurnDirection::Right, 1),
+                Movement::Forward(8)
+            ],
+            route
+        );
+
+        let route = plan_route(ship, (-5, 0), PlanConstraints::FORWARD_AND_TURNS).unwrap();
+        assert_eq!(
+            vec![Movement::Turn(TurnDirection::Left, 2), Movement::Forward(5)],
+            route
+        );
+
+        let no_turns = PlanConstraints {
+            moves: false,
+            turns: false,
+            forward: true,
+        };
+        assert_eq!(None, plan_route(ship, (0, 5), no_turns));
+        assert_eq!(Some(vec![]), plan_route(ship, (0, 0), no_turns));
+    }
+
+    #[test]
+    fn plan_waypoint_route_test() {
+        let ship = ShipWithWayPoint {
+            x: 0,
+            y: 0,
+            wposition_x: 10,
+            wposition_y: 1,
+        };
+        let route = plan_waypoint_route(ship, (100, 10), PlanConstraints::ANY).unwrap();
+        assert_eq!(vec![Movement::Forward(10)], route);
+
+        let route = plan_waypoint_route(ship, (-20, 200), PlanConstraints::ANY).unwrap();
+        assert_eq!(
+            vec![
+                Movement::Turn(TurnDirection::Left, 1),
+                Movement::Forward(20)
+            ],
+            route
+        );
+
+        let route = plan_waypoint_route(ship, (70, 28), PlanConstraints::ANY).unwrap();
+        assert_eq!(
+            vec![Movement::Move(Direction::North, 3), Movement::Forward(7)],
+            route
+        );
+
+        let route = plan_waypoint_route(ship, (214, -72), PlanConstraints::ANY).unwrap();
+        assert_eq!(3, route.len());
+        assert!(validate_route(ship, &route, (214, -72)));
+
+        // 10 * (10, 1) + 3 * (-1, 10)
+        let route = plan_waypoint_route(ship, (97, 40), PlanConstraints::FORWARD_AND_TURNS);
+        assert_eq!(
+            Some(vec![
+                Movement::Forward(10),
+                Movement::Turn(TurnDirection::Left, 1),
+                Movement::Forward(3)
+            ]),
+            route
+        );
+        assert_eq!(
+            None,
+            plan_waypoint_route(ship, (1, 0), PlanConstraints::FORWARD_AND_TURNS)
+        );
+    }
+
+    #[test]
+    fn movement_display_test() {
+        let (_, movements) = parse_lines(TEST_STR).unwrap();
+        let text = movements
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(TEST_STR, text);
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 enum Movement {
-    Move(Direction, i32),
+    Move(Direction, i64),
     Turn(TurnDirection, i32),
-    Forward(i32),
+    Forward(i64),
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
@@ -106,144 +328,147 @@ enum Direction {
     West,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum NavigationError {
+    /// The line (numbered from 1) could not be read as a movement.
+    Parse {
+        line: usize,
+    },
+    /// A position left the range of an `i64` while applying movement `step` (numbered from 1).
+    /// When the movements are read with `fold_reader` it is the line number, like `Parse`.
+    Overflow {
+        step: usize,
+    },
+    Io(std::io::ErrorKind),
+}
+
+/// Something that can follow the navigation instructions.
+///
+/// Positions and headings are (x, y) with east and north positive. The heading is the
+/// vector travelled by a single `Forward(1)`. Movements return `None` on overflow.
+trait NavigationModel: Copy {
+    fn position(&self) -> (i64, i64);
+    fn heading(&self) -> (i64, i64);
+    fn move_in_direction(&self, direction: Direction, distance: i64) -> Option<Self>;
+    fn forward(&self, distance: i64) -> Option<Self>;
+    /// Turn by a number of right angles.
+    fn turn(&self, turn_direction: TurnDirection, turns: i32) -> Option<Self>;
+
+    fn action_movement(&self, movement: Movement) -> Option<Self> {
+        match movement {
+            Movement::Move(direction, distance) => self.move_in_direction(direction, distance),
+            Movement::Forward(distance) => self.forward(distance),
+            Movement::Turn(turn_direction, turns) => self.turn(turn_direction, turns),
+        }
+    }
+
+    /// `None` if the distance doesn't fit in a `u64`.
+    fn get_manhattan_distance(&self, position_x: i64, position_y: i64) -> Option<u64> {
+        let (x, y) = self.position();
+        position_x.abs_diff(x).checked_add(position_y.abs_diff(y))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 struct Ship {
     direction: Direction,
-    position_x: i32,
-    position_y: i32,
+    position_x: i64,
+    position_y: i64,
 }
 
-impl Ship {
-    fn move_in_direction(&self, direction: Direction, distance: i32) -> Self {
-        match direction {
-            Direction::North => Self {
-                direction: self.direction,
-                position_x: self.position_x,
-                position_y: self.position_y + distance,
-            },
-            Direction::South => Self {
-                direction: self.direction,
-                position_x: self.position_x,
-                position_y: self.position_y - distance,
-            },
-            Direction::East => Self {
-                direction: self.direction,
-                position_x: self.position_x + distance,
-                position_y: self.position_y,
-            },
-            Direction::West => Self {
-                direction: self.direction,
-                position_x: self.position_x - distance,
-                position_y: self.position_y,
-            },
-        }
+impl NavigationModel for Ship {
+    fn position(&self) -> (i64, i64) {
+        (self.position_x, self.position_y)
     }
 
-    fn action_movement(&self, movement: Movement) -> Self {
-        match movement {
-            Movement::Move(direction, distance) => self.move_in_direction(direction, distance),
-            Movement::Forward(distance) => {
-                let direction = self.direction;
-                self.move_in_direction(direction, distance)
-            }
-            Movement::Turn(turn_direction, turns) => self.turn(turn_direction, turns),
-        }
+    fn heading(&self) -> (i64, i64) {
+        self.direction.offset(1).unwrap()
+    }
+
+    fn move_in_direction(&self, direction: Direction, distance: i64) -> Option<Self> {
+        let (x, y) = direction.offset(distance)?;
+        Some(Self {
+            direction: self.direction,
+            position_x: self.position_x.checked_add(x)?,
+            position_y: self.position_y.checked_add(y)?,
+        })
     }
 
-    fn turn(&self, turn_direction: TurnDirection, turns: i32) -> Self {
+    fn forward(&self, distance: i64) -> Option<Self> {
+        self.move_in_direction(self.direction, distance)
+    }
+
+    fn turn(&self, turn_direction: TurnDirection, turns: i32) -> Option<Self> {
         let mut current_direction = self.direction;
-        for _ in 0..turns {
+        for _ in 0..turns.rem_euclid(4) {
             current_direction = current_direction.turn(turn_direction);
         }
-        Self {
+        Some(Self {
             direction: current_direction,
             position_x: self.position_x,
             position_y: self.position_y,
-        }
-    }
-
-    fn get_manhattan_distance(&self, position_x: i32, position_y: i32) -> i32 {
-        (position_x - self.position_x).abs() + (position_y - self.position_y).abs()
+        })
     }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
 struct ShipWithWayPoint {
-    x: i32,
-    y: i32,
-    wposition_x: i32,
-    wposition_y: i32,
+    x: i64,
+    y: i64,
+    wposition_x: i64,
+    wposition_y: i64,
 }
 
-impl ShipWithWayPoint {
-    fn move_in_direction(&self, direction: Direction, distance: i32) -> Self {
-        match direction {
-            Direction::North => Self {
-                x: self.x,
-                y: self.y,
-                wposition_x: self.wposition_x,
-                wposition_y: self.wposition_y + distance,
-            },
-            Direction::South => Self {
-                x: self.x,
-                y: self.y,
-                wposition_x: self.wposition_x,
-                wposition_y: self.wposition_y - distance,
-            },
-            Direction::East => Self {
-                x: self.x,
-                y: self.y,
-                wposition_x: self.wposition_x + distance,
-                wposition_y: self.wposition_y,
-            },
-            Direction::West => Self {
-                x: self.x,
-                y: self.y,
-                wposition_x: self.wposition_x - distance,
-                wposition_y: self.wposition_y,
-            },
-        }
+impl NavigationModel for ShipWithWayPoint {
+    fn position(&self) -> (i64, i64) {
+        (self.x, self.y)
     }
 
-    fn action_movement(&self, movement: Movement) -> Self {
-        match movement {
-            Movement::Move(direction, distance) => self.move_in_direction(direction, distance),
-            Movement::Forward(distance) => {
-                let mut dest = self.clone();
-                for _ in 0..distance {
-                    dest = Self {
-                        x: dest.wposition_x + dest.x,
-                        wposition_x: dest.wposition_x,
-                        y: dest.wposition_y + dest.y,
-                        wposition_y: dest.wposition_y,
-                    };
-                }
-                dest
-            }
-            Movement::Turn(turn_direction, turns) => self.turn(turn_direction, turns),
-        }
+    fn heading(&self) -> (i64, i64) {
+        (self.wposition_x, self.wposition_y)
+    }
+
+    fn move_in_direction(&self, direction: Direction, distance: i64) -> Option<Self> {
+        let (x, y) = direction.offset(distance)?;
+        Some(Self {
+            x: self.x,
+            y: self.y,
+            wposition_x: self.wposition_x.checked_add(x)?,
+            wposition_y: self.wposition_y.checked_add(y)?,
+        })
+    }
+
+    fn forward(&self, distance: i64) -> Option<Self> {
+        Some(Self {
+            x: self
+                .wposition_x
+                .checked_mul(distance)?
+                .checked_add(self.x)?,
+            y: self
+                .wposition_y
+                .checked_mul(distance)?
+                .checked_add(self.y)?,
+            wposition_x: self.wposition_x,
+            wposition_y: self.wposition_y,
+        })
     }
 
-    fn turn(&self, turn_direction: TurnDirection, turns: i32) -> Self {
+    fn turn(&self, turn_direction: TurnDirection, turns: i32) -> Option<Self> {
         let mut position_x = self.wposition_x;
         let mut position_y = self.wposition_y;
 
-        for _ in 0..turns {
+        for _ in 0..turns.rem_euclid(4) {
             (position_x, position_y) = match turn_direction {
-                TurnDirection::Left => (-position_y, position_x),
-                TurnDirection::Right => (position_y, -position_x),
+                TurnDirection::Left => (position_y.checked_neg()?, position_x),
+                TurnDirection::Right => (position_y, position_x.checked_neg()?),
             };
         }
-        Self {
+        Some(Self {
             x: self.x,
             y: self.y,
             wposition_x: position_x,
             wposition_y: position_y,
-        }
-    }
-
-    fn get_manhattan_distance(&self, position_x: i32, position_y: i32) -> i32 {
-        (position_x - self.x).abs() + (position_y - self.y).abs()
+        })
     }
 }
 
@@ -265,60 +490,486 @@ impl Direction {
             }
         }
     }
+
+    fn offset(&self, distance: i64) -> Option<(i64, i64)> {
+        Some(match *self {
+            Direction::North => (0, distance),
+            Direction::South => (0, distance.checked_neg()?),
+            Direction::East => (distance, 0),
+            Direction::West => (distance.checked_neg()?, 0),
+        })
+    }
+
+    /// Number of left turns from East.
+    fn quarter(&self) -> i32 {
+        match *self {
+            Direction::East => 0,
+            Direction::North => 1,
+            Direction::West => 2,
+            Direction::South => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for Movement {
+    /// Formats the movement as a line of puzzle input.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Movement::Move(Direction::North, n) => write!(f, "N{}", n),
+            Movement::Move(Direction::South, n) => write!(f, "S{}", n),
+            Movement::Move(Direction::East, n) => write!(f, "E{}", n),
+            Movement::Move(Direction::West, n) => write!(f, "W{}", n),
+            Movement::Turn(TurnDirection::Left, turns) => write!(f, "L{}", turns * 90),
+            Movement::Turn(TurnDirection::Right, turns) => write!(f, "R{}", turns * 90),
+            Movement::Forward(n) => write!(f, "F{}", n),
+        }
+    }
+}
+
+/// Apply each movement in turn without keeping the intermediate states, so the movements
+/// can come from a lazy iterator of any length.
+fn fold_movements<M: NavigationModel>(
+    start: M,
+    movements: impl IntoIterator<Item = Movement>,
+) -> Result<M, NavigationError> {
+    movements
+        .into_iter()
+        .enumerate()
+        .try_fold(start, |ship, (i, m)| {
+            ship.action_movement(m)
+                .ok_or(NavigationError::Overflow { step: i + 1 })
+        })
+}
+
+/// Read, parse and apply movements a line at a time.
+fn fold_reader<M: NavigationModel>(
+    start: M,
+    reader: impl std::io::BufRead,
+) -> Result<M, NavigationError> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if l.is_empty()))
+        .try_fold(start, |ship, (i, line)| {
+            let line = line.map_err(|e| NavigationError::Io(e.kind()))?;
+            match parse_line(&line) {
+                Ok(("", movement)) => ship
+                    .action_movement(movement)
+                    .ok_or(NavigationError::Overflow { step: i + 1 }),
+                _ => Err(NavigationError::Parse { line: i + 1 }),
+            }
+        })
+}
+
+/// Every state a ship passed through, starting with where it began.
+struct Trajectory<M: NavigationModel> {
+    states: Vec<M>,
+}
+
+impl<M: NavigationModel> Trajectory<M> {
+    fn navigate(
+        start: M,
+        movements: impl IntoIterator<Item = Movement>,
+    ) -> Result<Self, NavigationError> {
+        let mut states = vec![start];
+        let mut current = start;
+        for (i, m) in movements.into_iter().enumerate() {
+            current = current
+                .action_movement(m)
+                .ok_or(NavigationError::Overflow { step: i + 1 })?;
+            states.push(current);
+        }
+        Ok(Self { states })
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("step,x,y,heading_x,heading_y\n");
+        for (step, state) in self.states.iter().enumerate() {
+            let (x, y) = state.position();
+            let (heading_x, heading_y) = state.heading();
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                step, x, y, heading_x, heading_y
+            ));
+        }
+        csv
+    }
+
+    /// Render the route as a polyline. SVG y grows downwards so north is flipped to be up.
+    fn to_svg(&self) -> String {
+        let points = self
+            .states
+            .iter()
+            .map(|s| s.position())
+            .map(|(x, y)| (x, -y))
+            .collect::<Vec<_>>();
+        let min_x = points.iter().map(|p| p.0).min().unwrap();
+        let max_x = points.iter().map(|p| p.0).max().unwrap();
+        let min_y = points.iter().map(|p| p.1).min().unwrap();
+        let max_y = points.iter().map(|p| p.1).max().unwrap();
+        let margin = ((max_x - min_x).max(max_y - min_y) / 20).max(1);
+        let stroke = (margin / 4).max(1);
+        let (start, end) = (points[0], points[points.len() - 1]);
+        let polyline = points
+            .iter()
+            .map(|(x, y)| format!("{},{}", x, y))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min_x - margin,
+            min_y - margin,
+            max_x - min_x + 2 * margin,
+            max_y - min_y + 2 * margin
+        );
+        svg.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"black\" stroke-width=\"{}\" points=\"{}\"/>\n",
+            stroke, polyline
+        ));
+        svg.push_str(&format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"green\"/>\n",
+            start.0,
+            start.1,
+            stroke * 2
+        ));
+        svg.push_str(&format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"red\"/>\n",
+            end.0,
+            end.1,
+            stroke * 2
+        ));
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Which kinds of instruction a planned route may use.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+struct PlanConstraints {
+    moves: bool,
+    turns: bool,
+    forward: bool,
 }
 
-fn parse_lines<'a>(input: &'a str) -> nom::IResult<&'a str, Vec<Movement>> {
+impl PlanConstraints {
+    const ANY: Self = Self {
+        moves: true,
+        turns: true,
+        forward: true,
+    };
+    const FORWARD_AND_TURNS: Self = Self {
+        moves: false,
+        turns: true,
+        forward: true,
+    };
+}
+
+fn turn_between(from: i32, to: i32) -> Option<Movement> {
+    match (to - from).rem_euclid(4) {
+        1 => Some(Movement::Turn(TurnDirection::Left, 1)),
+        2 => Some(Movement::Turn(TurnDirection::Left, 2)),
+        3 => Some(Movement::Turn(TurnDirection::Right, 1)),
+        _ => None,
+    }
+}
+
+fn axis_move(positive: Direction, negative: Direction, delta: i64) -> Option<Movement> {
+    match delta {
+        0 => None,
+        d if d > 0 => Some(Movement::Move(positive, d)),
+        d => Some(Movement::Move(negative, d.checked_neg()?)),
+    }
+}
+
+/// Shortest run of turns and forward moves covering every segment. Segments are a heading,
+/// as a count of left turns, and a distance; they can be travelled in either order.
+fn plan_forward_segments(
+    heading: i32,
+    segments: &[(i32, i64)],
+    turns: bool,
+) -> Option<Vec<Movement>> {
+    let mut orders = vec![segments.to_vec()];
+    if segments.len() > 1 {
+        orders.push(segments.iter().rev().copied().collect());
+    }
+    orders
+        .into_iter()
+        .filter_map(|order| {
+            let mut heading = heading;
+            let mut route = Vec::new();
+            for (quarter, distance) in order {
+                if let Some(turn) = turn_between(heading, quarter) {
+                    if !turns {
+                        return None;
+                    }
+                    route.push(turn);
+                }
+                heading = quarter;
+                route.push(Movement::Forward(distance));
+            }
+            Some(route)
+        })
+        .min_by_key(|route| route.len())
+}
+
+/// Replay a route and check it finishes at the target.
+fn validate_route<M: NavigationModel>(start: M, route: &[Movement], target: (i64, i64)) -> bool {
+    fold_movements(start, route.iter().copied()).is_ok_and(|m| m.position() == target)
+}
+
+/// Find a shortest list of instructions that takes the ship to the target.
+fn plan_route(
+    start: Ship,
+    target: (i64, i64),
+    constraints: PlanConstraints,
+) -> Option<Vec<Movement>> {
+    let dx = target.0.checked_sub(start.position_x)?;
+    let dy = target.1.checked_sub(start.position_y)?;
+    let segments = [
+        (
+            if dx > 0 {
+                Direction::East
+            } else {
+                Direction::West
+            },
+            dx,
+        ),
+        (
+            if dy > 0 {
+                Direction::North
+            } else {
+                Direction::South
+            },
+            dy,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, d)| *d != 0)
+    .map(|(direction, d)| Some((direction, d.checked_abs()?)))
+    .collect::<Option<Vec<_>>>()?;
+
+    // Each segment is either a direct move or a forward move, possibly after turning.
+    let mut best: Option<Vec<Movement>> = None;
+    for mask in 0..(1 << segments.len()) {
+        let (moved, forward): (Vec<_>, Vec<_>) = segments
+            .iter()
+            .enumerate()
+            .partition(|(i, _)| mask & (1 << i) != 0);
+        if (!moved.is_empty() && !constraints.moves)
+            || (!forward.is_empty() && !constraints.forward)
+        {
+            continue;
+        }
+        let mut route = moved
+            .iter()
+            .map(|(_, (direction, d))| Movement::Move(*direction, *d))
+            .collect::<Vec<_>>();
+        let forward = forward
+            .iter()
+            .map(|(_, (direction, d))| (direction.quarter(), *d))
+            .collect::<Vec<_>>();
+        match plan_forward_segments(start.direction.quarter(), &forward, constraints.turns) {
+            Some(r) => route.extend(r),
+            None => continue,
+        }
+        if best.as_ref().is_none_or(|b| route.len() < b.len()) {
+            best = Some(route);
+        }
+    }
+    best.filter(|route| validate_route(start, route, target))
+}
+
+/// Find a shortest list of instructions that takes the ship with a waypoint to the target.
+///
+/// Only forward moves move the ship, so every route ends with one. A route needs at most three
+/// instructions when the waypoint can be moved: set the waypoint to the offset, then `F1`.
+fn plan_waypoint_route(
+    start: ShipWithWayPoint,
+    target: (i64, i64),
+    constraints: PlanConstraints,
+) -> Option<Vec<Movement>> {
+    let dx = target.0.checked_sub(start.x)?;
+    let dy = target.1.checked_sub(start.y)?;
+    let (wx, wy) = (start.wposition_x, start.wposition_y);
+    if (dx, dy) == (0, 0) {
+        return Some(Vec::new());
+    }
+    if !constraints.forward {
+        return None;
+    }
+
+    let mut candidates = Vec::new();
+
+    // Turning only ever rotates the waypoint, so the ship can reach p * w + q * left(w).
+    let (dx2, dy2, wx2, wy2) = (dx as i128, dy as i128, wx as i128, wy as i128);
+    let det = wx2 * wx2 + wy2 * wy2;
+    if det != 0 {
+        let p = dx2 * wx2 + dy2 * wy2;
+        let q = dy2 * wx2 - dx2 * wy2;
+        if p % det == 0 && q % det == 0 {
+            let segments = [(0, p / det), (1, q / det)]
+                .into_iter()
+                .filter(|(_, n)| *n != 0)
+                .map(|(quarter, n)| {
+                    let quarter = if n < 0 { quarter + 2 } else { quarter };
+                    Some((quarter, i64::try_from(n.abs()).ok()?))
+                })
+                .collect::<Option<Vec<_>>>();
+            if let Some(r) = segments.and_then(|s| plan_forward_segments(0, &s, constraints.turns))
+            {
+                candidates.push(r);
+            }
+        }
+    }
+
+    if constraints.moves {
+        // Move the waypoint along one axis so a whole number of steps lands on the target.
+        let steps = |fixed: i64, delta: i64| match fixed {
+            0 if delta == 0 => Some(1),
+            0 => None,
+            f if delta.checked_rem(f) == Some(0) => delta.checked_div(f).filter(|n| *n > 0),
+            _ => None,
+        };
+        let single_moves = [
+            steps(wy, dy).filter(|n| dx % n == 0).and_then(|n| {
+                let delta = (dx / n).checked_sub(wx)?;
+                Some((n, Direction::East, Direction::West, delta))
+            }),
+            steps(wx, dx).filter(|n| dy % n == 0).and_then(|n| {
+                let delta = (dy / n).checked_sub(wy)?;
+                Some((n, Direction::North, Direction::South, delta))
+            }),
+        ];
+        for (n, positive, negative, delta) in single_moves.into_iter().flatten() {
+            let mut route = Vec::from_iter(axis_move(positive, negative, delta));
+            route.push(Movement::Forward(n));
+            candidates.push(route);
+        }
+
+        let mut route = Vec::new();
+        route.extend(axis_move(
+            Direction::East,
+            Direction::West,
+            dx.checked_sub(wx)?,
+        ));
+        route.extend(axis_move(
+            Direction::North,
+            Direction::South,
+            dy.checked_sub(wy)?,
+        ));
+        route.push(Movement::Forward(1));
+        candidates.push(route);
+    }
+
+    candidates
+        .into_iter()
+        .filter(|route| validate_route(start, route, target))
+        .min_by_key(|route| route.len())
+}
+
+fn parse_lines(input: &str) -> nom::IResult<&str, Vec<Movement>> {
     use nom::character::complete::newline;
     use nom::multi::separated_list0;
 
     separated_list0(newline, parse_line)(input)
 }
 
-fn parse_line<'a>(input: &'a str) -> nom::IResult<&'a str, Movement> {
+fn parse_line(input: &str) -> nom::IResult<&str, Movement> {
     use nom::character::complete::anychar;
     use nom::character::complete::digit1;
+    use nom::error::{Error, ErrorKind};
 
+    let line = input;
     let (input, command) = anychar(input)?;
     let (input, digits) = digit1(input)?;
 
-    let number = digits.parse::<i32>().unwrap();
+    let number = match digits.parse::<i64>() {
+        Ok(number) => number,
+        Err(_) => return Err(nom::Err::Failure(Error::new(line, ErrorKind::Digit))),
+    };
+    let turns = ((number / 90) % 4) as i32;
 
+    // Only right angle turns are supported, anything else can't be represented.
     let movement = match command {
-        'L' => Movement::Turn(TurnDirection::Left, number / 90),
-        'R' => Movement::Turn(TurnDirection::Right, number / 90),
+        'L' | 'R' if number % 90 != 0 => {
+            return Err(nom::Err::Failure(Error::new(line, ErrorKind::Verify)))
+        }
+        'L' => Movement::Turn(TurnDirection::Left, turns),
+        'R' => Movement::Turn(TurnDirection::Right, turns),
         'F' => Movement::Forward(number),
         'N' => Movement::Move(Direction::North, number),
         'S' => Movement::Move(Direction::South, number),
         'E' => Movement::Move(Direction::East, number),
         'W' => Movement::Move(Direction::West, number),
-        _ => panic!(),
+        _ => return Err(nom::Err::Failure(Error::new(line, ErrorKind::Char))),
     };
     Ok((input, movement))
 }
 
 fn main() {
-    let (_, movements) = parse_lines(PUZZLE_INPUT_STR).unwrap();
-
-    let mut ship = Ship {
+    let ship = Ship {
         direction: Direction::East,
         position_x: 0,
         position_y: 0,
     };
-    for m in movements.clone() {
-        ship = ship.action_movement(m);
-    }
-    let md = ship.get_manhattan_distance(0, 0);
-    println!("{}", md);
-
-    let mut ship = ShipWithWayPoint {
+    let ship_with_waypoint = ShipWithWayPoint {
         x: 0,
         y: 0,
         wposition_x: 10,
         wposition_y: 1,
     };
-    for m in movements {
-        ship = ship.action_movement(m);
+
+    // Optionally dump a route for plotting instead, e.g. `day12 svg waypoint > route.svg`.
+    let args = std::env::args().collect::<Vec<_>>();
+    let waypoint = args.get(2).map(|s| s.as_str()) == Some("waypoint");
+    let export = args.get(1).map(|s| s.as_str());
+    if let Some(format @ ("csv" | "svg")) = export {
+        let (_, movements) = parse_lines(PUZZLE_INPUT_STR).unwrap();
+        let output = match (format, waypoint) {
+            ("csv", false) => Trajectory::navigate(ship, movements).unwrap().to_csv(),
+            ("csv", true) => Trajectory::navigate(ship_with_waypoint, movements)
+                .unwrap()
+                .to_csv(),
+            (_, false) => Trajectory::navigate(ship, movements).unwrap().to_svg(),
+            (_, true) => Trajectory::navigate(ship_with_waypoint, movements)
+                .unwrap()
+                .to_svg(),
+        };
+        print!("{}", output);
+        return;
+    }
+
+    // Generate instructions that reach a point, e.g. `day12 route 17 -8 waypoint turns`.
+    if export == Some("route") {
+        let target = (args[2].parse().unwrap(), args[3].parse().unwrap());
+        let flags = &args[4..];
+        let constraints = if flags.iter().any(|f| f == "turns") {
+            PlanConstraints::FORWARD_AND_TURNS
+        } else {
+            PlanConstraints::ANY
+        };
+        let route = if flags.iter().any(|f| f == "waypoint") {
+            plan_waypoint_route(ship_with_waypoint, target, constraints)
+        } else {
+            plan_route(ship, target, constraints)
+        };
+        match route {
+            Some(route) => route.iter().for_each(|m| println!("{}", m)),
+            None => println!("unreachable"),
+        }
+        return;
     }
-    let md = ship.get_manhattan_distance(0, 0);
+
+    let ship = fold_reader(ship, PUZZLE_INPUT_STR.as_bytes()).unwrap();
+    let md = ship
+        .get_manhattan_distance(0, 0)
+        .expect("distance overflowed");
+    println!("{}", md);
+
+    let ship = fold_reader(ship_with_waypoint, PUZZLE_INPUT_STR.as_bytes()).unwrap();
+    let md = ship
+        .get_manhattan_distance(0, 0)
+        .expect("distance overflowed");
     println!("{}", md);
 }
@@ -1,3 +1,7 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::io::BufRead;
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -16,53 +20,355 @@ mod test {
 9000
 
 10000";
+
+    fn total_calories_of_top_x_elves(input: &str, x: usize) -> Result<u64, CalorieError> {
+        Ok(top_elves(input.as_bytes(), x)?
+            .iter()
+            .map(|(_, total)| total)
+            .sum())
+    }
+
     #[test]
     fn total_calories_elf_carrying_confirm() {
-        assert_eq!(24000, total_calories_elf_carrying(TEST_INPUT));
+        assert_eq!(Ok(24000), total_calories_of_top_x_elves(TEST_INPUT, 1));
     }
 
     #[test]
     fn total_calories_top_3_elves_carrying_confirm() {
-        assert_eq!(45000, total_calories_of_top_x_elves(TEST_INPUT, 3));
+        assert_eq!(Ok(45000), total_calories_of_top_x_elves(TEST_INPUT, 3));
     }
     #[test]
     fn total_calories_top_1_elves_carrying_confirm() {
-        assert_eq!(24000, total_calories_of_top_x_elves(TEST_INPUT, 1));
+        assert_eq!(Ok(24000), total_calories_of_top_x_elves(TEST_INPUT, 1));
+    }
+
+    #[test]
+    fn elves_confirm() {
+        let elves = Elves::new(TEST_INPUT.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(5, elves.len());
+        assert_eq!((3, 10), (elves[3].index, elves[3].line));
+        assert_eq!(3, elves[3].count());
+        assert_eq!(24000, elves[3].total());
+        assert_eq!((7000, 9000), (elves[3].min(), elves[3].max()));
+        assert_eq!(8000.0, elves[3].median());
+        assert_eq!(5500.0, elves[2].median());
+        assert_eq!(7000, elves[3].percentile(25.0));
+        assert_eq!(9000, elves[3].percentile(90.0));
+    }
+
+    #[test]
+    fn top_elves_confirm() {
+        let mut top = TopElves::new(2);
+        for elf in Elves::new(TEST_INPUT.as_bytes()) {
+            top.push(&elf.unwrap());
+        }
+        assert_eq!(vec![(3, 24000), (2, 11000)], top.into_sorted());
+    }
+
+    #[test]
+    fn malformed_lines_confirm() {
+        let input = "1000\ntwo\n\n\n\n3000\n4x\n5000\n";
+        let mut seen = Vec::new();
+        let inventory = summarise(input.as_bytes(), 3, |elf| seen.push(elf.index)).unwrap();
+        assert_eq!(vec![0, 1], seen);
+        assert_eq!(2, inventory.elves);
+        assert_eq!(vec![(1, 8000), (0, 1000)], inventory.top);
+        assert_eq!(
+            vec![
+                CalorieError::Malformed {
+                    line: 2,
+                    text: "two".to_string()
+                },
+                CalorieError::Malformed {
+                    line: 7,
+                    text: "4x".to_string()
+                }
+            ],
+            inventory.malformed
+        );
+        assert_eq!(
+            Err(CalorieError::Malformed {
+                line: 2,
+                text: "two".to_string()
+            }),
+            total_calories_of_top_x_elves(input, 1)
+        );
+    }
+
+    #[test]
+    fn large_total_confirm() {
+        let input = format!("{}\n{}\n\n1", u32::MAX, u32::MAX);
+        assert_eq!(
+            Ok(vec![(0, 2 * u32::MAX as u64), (1, 1)]),
+            top_elves(input.as_bytes(), 3)
+        );
+    }
+
+    #[test]
+    fn read_error_confirm() {
+        struct Failing;
+        impl std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+        }
+        let elves = Elves::new(std::io::BufReader::new(Failing)).collect::<Vec<_>>();
+        assert_eq!(
+            vec![Err(CalorieError::Io(std::io::ErrorKind::BrokenPipe))],
+            elves
+        );
+        assert_eq!(
+            Err(CalorieError::Io(std::io::ErrorKind::BrokenPipe)),
+            summarise(std::io::BufReader::new(Failing), 3, |_| {})
+        );
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum CalorieError {
+    /// `line` is numbered from 1.
+    Malformed {
+        line: usize,
+        text: String,
+    },
+    Io(std::io::ErrorKind),
+}
+
+/// The items carried by one elf. `index` counts elves from 0 in the order they appear and
+/// `line` is the line number of their first item.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Elf {
+    index: usize,
+    line: usize,
+    /// Sorted smallest first.
+    items: Vec<u32>,
+}
+
+impl Elf {
+    fn count(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Wider than the items, so no number of them can overflow it.
+    fn total(&self) -> u64 {
+        self.items.iter().map(|&item| u64::from(item)).sum()
+    }
+
+    fn min(&self) -> u32 {
+        self.items[0]
+    }
+
+    fn max(&self) -> u32 {
+        self.items[self.items.len() - 1]
+    }
+
+    fn median(&self) -> f64 {
+        let middle = self.items.len() / 2;
+        if self.items.len().is_multiple_of(2) {
+            (self.items[middle - 1] as f64 + self.items[middle] as f64) / 2.0
+        } else {
+            self.items[middle] as f64
+        }
+    }
+
+    /// Nearest rank percentile, `p` is from 0 to 100.
+    fn percentile(&self, p: f64) -> u32 {
+        let rank = (p / 100.0 * self.items.len() as f64).ceil() as usize;
+        self.items[rank.clamp(1, self.items.len()) - 1]
+    }
+}
+
+/// Reads one elf at a time from any reader. A malformed line is returned as an error without
+/// ending the iteration, ahead of the elf it was found in. A read error ends the iteration.
+struct Elves<R> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    index: usize,
+    pending: VecDeque<Result<Elf, CalorieError>>,
+    /// Set once reading has failed.
+    failed: bool,
+}
+
+impl<R: BufRead> Elves<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            line: 0,
+            index: 0,
+            pending: VecDeque::new(),
+            failed: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Elves<R> {
+    type Item = Result<Elf, CalorieError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(pending) = self.pending.pop_front() {
+            return Some(pending);
+        }
+        if self.failed {
+            return None;
+        }
+        let mut elf = Elf {
+            index: self.index,
+            line: 0,
+            items: Vec::new(),
+        };
+        let mut started = false;
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    self.failed = true;
+                    self.pending.push_back(Err(CalorieError::Io(e.kind())));
+                    return self.pending.pop_front();
+                }
+                None => break,
+            };
+            self.line += 1;
+            if line.is_empty() {
+                if started {
+                    break;
+                }
+                continue;
+            }
+            started = true;
+            match line.parse::<u32>() {
+                Ok(calories) => {
+                    if elf.items.is_empty() {
+                        elf.line = self.line;
+                    }
+                    elf.items.push(calories);
+                }
+                Err(_) => self.pending.push_back(Err(CalorieError::Malformed {
+                    line: self.line,
+                    text: line,
+                })),
+            }
+        }
+        if !elf.items.is_empty() {
+            self.index += 1;
+            elf.items.sort();
+            self.pending.push_back(Ok(elf));
+        }
+        self.pending.pop_front()
     }
 }
 
-fn total_calories_elf_carrying(input: &str) -> u32 {
-    let elves = input.split("\n\n");
-    let mut elves_carrying = elves
-        .map(|e| e.lines().map(|l| l.parse::<u32>().unwrap()).sum())
-        .collect::<Vec<u32>>();
-    let iter = elves_carrying.iter_mut();
-    iter.max_by(|x, y| x.cmp(y)).unwrap().clone()
+/// Keeps the largest `k` totals seen so far, as (elf index, total).
+struct TopElves {
+    k: usize,
+    heap: BinaryHeap<Reverse<(u64, Reverse<usize>)>>,
 }
 
-fn total_calories_of_top_x_elves(input: &str, x: usize) -> u32 {
-    let elves_carrying = total_calories_each_elf_carrying(input);
-    elves_carrying.iter().take(x).sum::<u32>()
+impl TopElves {
+    fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k + 1),
+        }
+    }
+
+    fn push(&mut self, elf: &Elf) {
+        self.heap.push(Reverse((elf.total(), Reverse(elf.index))));
+        if self.heap.len() > self.k {
+            self.heap.pop();
+        }
+    }
+
+    /// Largest total first.
+    fn into_sorted(self) -> Vec<(usize, u64)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((total, Reverse(index)))| (index, total))
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Inventory {
+    elves: usize,
+    top: Vec<(usize, u64)>,
+    malformed: Vec<CalorieError>,
 }
 
-fn total_calories_each_elf_carrying(input: &str) -> Vec<u32> {
-    let elves = input.split("\n\n");
-    let mut elves_carrying = elves
-        .map(|e| e.lines().map(|l| l.parse::<u32>().unwrap()).sum())
-        .collect::<Vec<u32>>();
-    elves_carrying.sort_by(|x, y| y.cmp(x));
-    elves_carrying
+/// Read every elf, keeping the top `k` and the malformed lines, and handing each elf to
+/// `on_elf` as it is read.
+fn summarise(
+    reader: impl BufRead,
+    k: usize,
+    mut on_elf: impl FnMut(&Elf),
+) -> Result<Inventory, CalorieError> {
+    let mut top = TopElves::new(k);
+    let mut elves = 0;
+    let mut malformed = Vec::new();
+    for elf in Elves::new(reader) {
+        match elf {
+            Ok(elf) => {
+                elves += 1;
+                top.push(&elf);
+                on_elf(&elf);
+            }
+            Err(e @ CalorieError::Malformed { .. }) => malformed.push(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Inventory {
+        elves,
+        top: top.into_sorted(),
+        malformed,
+    })
 }
+
+/// The `k` elves carrying the most as (elf index, total), largest first, stopping at the first
+/// malformed line.
+fn top_elves(reader: impl BufRead, k: usize) -> Result<Vec<(usize, u64)>, CalorieError> {
+    let mut top = TopElves::new(k);
+    for elf in Elves::new(reader) {
+        top.push(&elf?);
+    }
+    Ok(top.into_sorted())
+}
+
 fn main() -> std::io::Result<()> {
     use std::fs::File;
-    use std::io::prelude::*;
+    use std::io::BufReader;
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path)?;
-    let mut input = String::new();
-    file.read_to_string(&mut input)?;
-    println!("{}", total_calories_elf_carrying(&input));
-    println!("{}", total_calories_of_top_x_elves(&input, 3));
+
+    // `stats` streams the input and prints the items carried by every elf instead.
+    if std::env::args().nth(1).as_deref() == Some("stats") {
+        println!("elf,line,count,total,min,max,median,p90");
+        let print_elf = |elf: &Elf| {
+            println!(
+                "{},{},{},{},{},{},{},{}",
+                elf.index,
+                elf.line,
+                elf.count(),
+                elf.total(),
+                elf.min(),
+                elf.max(),
+                elf.median(),
+                elf.percentile(90.0)
+            )
+        };
+        let inventory = summarise(BufReader::new(File::open(path)?), 3, print_elf)
+            .map_err(|e| std::io::Error::other(format!("{:?}", e)))?;
+        for e in inventory.malformed.iter() {
+            eprintln!("{:?}", e);
+        }
+        println!("{} elves, top 3 {:?}", inventory.elves, inventory.top);
+        return Ok(());
+    }
+
+    let top = top_elves(BufReader::new(File::open(path)?), 3).unwrap();
+    println!("{}", top.first().map_or(0, |(_, total)| *total));
+    println!("{}", top.iter().map(|(_, total)| total).sum::<u64>());
     Ok(())
 }
@@ -1,23 +1,30 @@
+use serde::Serialize;
+use std::sync::OnceLock;
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    const ROCK: Shape = Shape(0);
+    const PAPER: Shape = Shape(1);
+    const SCISSORS: Shape = Shape(2);
+
     const INPUT: &str = "A Y
 B X
 C Z";
 
     #[test]
     fn win_lose_score_test() {
-        let lines = INPUT.split("\n");
+        let lines = INPUT.split('\n');
         assert_eq!(
             15u32,
-            lines.map(|l| rock_paper_scissors_play_game_part1(l)).sum()
+            lines.map(rock_paper_scissors_play_game_part1).sum::<u32>()
         )
     }
 
     #[test]
     fn rock_paper_scissors_get_characters_test() {
-        let mut lines = INPUT.split("\n");
+        let mut lines = INPUT.split('\n');
         let line = lines.next().unwrap();
         assert_eq!((ROCK, PAPER), rock_paper_scissors_get_characters(line));
         let line = lines.next().unwrap();
@@ -31,120 +38,651 @@ C Z";
 
     #[test]
     fn rock_paper_scissors_test_part2() {
-        let lines = INPUT.split("\n");
+        let lines = INPUT.split('\n');
         assert_eq!(
             12u32,
-            lines.map(|l| rock_paper_scissors_play_game_part2(l)).sum()
+            lines.map(rock_paper_scissors_play_game_part2).sum::<u32>()
         )
     }
+
+    #[test]
+    fn game_new_test() {
+        assert_eq!(
+            Err(GameError::EvenShapeCount(4)),
+            Game::new(&["a", "b", "c", "d"])
+        );
+        assert_eq!(Err(GameError::ShapeCount(1)), Game::new(&["rock"]));
+        assert_eq!(Err(GameError::ShapeCount(0)), Game::new(&[]));
+        let names = (0..27).map(|i| i.to_string()).collect::<Vec<_>>();
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(Err(GameError::ShapeCount(27)), Game::new(&names));
+
+        // The largest game still has a letter for every shape.
+        let game = Game::new(&names[..25]).unwrap();
+        let decoder = GuideDecoder::shapes(&game);
+        assert_eq!(Some(&'Y'), decoder.opponent.last());
+        assert_eq!('B', decoder.responses[0].0);
+        assert_eq!(
+            Err(GameError::ShapeScoreCount {
+                expected: 3,
+                found: 2
+            }),
+            Game::rock_paper_scissors().with_shape_scores(vec![1, 2])
+        );
+    }
+
+    #[test]
+    fn lizard_spock_test() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let names = game.names.clone();
+        let shape = |name| Shape(names.iter().position(|n| n == name).unwrap());
+        let wins = [
+            ("scissors", "paper"),
+            ("paper", "rock"),
+            ("rock", "lizard"),
+            ("lizard", "spock"),
+            ("spock", "scissors"),
+            ("scissors", "lizard"),
+            ("lizard", "paper"),
+            ("paper", "spock"),
+            ("spock", "rock"),
+            ("rock", "scissors"),
+        ];
+        for (winner, loser) in wins {
+            assert_eq!(Outcome::Win, game.outcome(shape(loser), shape(winner)));
+            assert_eq!(Outcome::Loss, game.outcome(shape(winner), shape(loser)));
+        }
+        for s in game.shapes() {
+            assert_eq!(Outcome::Draw, game.outcome(s, s));
+        }
+
+        // Both paper and lizard beat spock, lizard scores more so is chosen.
+        assert_eq!(
+            shape("lizard"),
+            game.shape_for(shape("spock"), Outcome::Win)
+        );
+        let game = game.with_outcome_scores(1, 2, 10);
+        assert_eq!(10 + 5, game.score(shape("paper"), shape("lizard")));
+    }
+
+    #[test]
+    fn decoder_test() {
+        let game = Game::rock_paper_scissors_lizard_spock();
+        let decoder = GuideDecoder::shapes(&game);
+        assert_eq!(
+            Ok((Shape(4), Response::Shape(Shape(0)))),
+            decoder.decode("E V")
+        );
+        assert_eq!(
+            Err(GameError::InvalidLine("F V".to_string())),
+            decoder.decode("F V")
+        );
+
+        let decoder = GuideDecoder::outcomes(&game);
+        assert_eq!(
+            Ok((Shape(1), Response::Outcome(Outcome::Win))),
+            decoder.decode("B Z")
+        );
+        assert_eq!(
+            Err(GameError::InvalidLine("B V".to_string())),
+            decoder.decode("B V")
+        );
+        assert!(decoder.decode("B Z extra").is_err());
+    }
+
+    /// Every way of assigning the second column's letters, which the analysis must agree with.
+    fn permutations(decoder: &GuideDecoder) -> Vec<GuideDecoder> {
+        fn permute(items: &[Response]) -> Vec<Vec<Response>> {
+            if items.len() <= 1 {
+                return vec![items.to_vec()];
+            }
+            let mut all = Vec::new();
+            for i in 0..items.len() {
+                let mut rest = items.to_vec();
+                let first = rest.remove(i);
+                for mut p in permute(&rest) {
+                    p.insert(0, first);
+                    all.push(p);
+                }
+            }
+            all
+        }
+        let letters = decoder.responses.iter().map(|(c, _)| *c);
+        let responses = decoder
+            .responses
+            .iter()
+            .map(|(_, r)| *r)
+            .collect::<Vec<_>>();
+        permute(&responses)
+            .into_iter()
+            .map(|p| GuideDecoder {
+                opponent: decoder.opponent.clone(),
+                responses: letters.clone().zip(p).collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn analyse_larger_games_test() {
+        // Pseudo-random guides, the five shape one checked against every assignment.
+        let guide = |game: &Game, decoder: &GuideDecoder, rounds: usize| {
+            let mut state = 17u64;
+            (0..rounds)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    let opponent = decoder.opponent[(state >> 33) as usize % game.names.len()];
+                    let response =
+                        decoder.responses[(state >> 45) as usize % decoder.responses.len()];
+                    format!("{} {}", opponent, response.0)
+                })
+                .collect::<Vec<_>>()
+        };
+        let game = Game::rock_paper_scissors_lizard_spock();
+        for decoder in [GuideDecoder::shapes(&game), GuideDecoder::outcomes(&game)] {
+            let lines = guide(&game, &decoder, 50);
+            let analysis = analyse_decoding(&game, &decoder, &lines).unwrap();
+            let totals = permutations(&decoder)
+                .iter()
+                .map(|p| {
+                    score_rounds(&game, p, &lines)
+                        .unwrap()
+                        .iter()
+                        .map(|r| r.shape_points + r.outcome_points)
+                        .sum::<u32>()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(*totals.iter().max().unwrap(), analysis.best.total);
+            assert_eq!(*totals.iter().min().unwrap(), analysis.worst.total);
+            for mapping in [&analysis.best, &analysis.worst] {
+                let decoder = GuideDecoder {
+                    opponent: decoder.opponent.clone(),
+                    responses: mapping.mapping.clone(),
+                };
+                let total = score_rounds(&game, &decoder, &lines)
+                    .unwrap()
+                    .iter()
+                    .map(|r| r.shape_points + r.outcome_points)
+                    .sum::<u32>();
+                assert_eq!(mapping.total, total);
+            }
+        }
+
+        // Far too many assignments to try them all.
+        let names = (0..MAX_SHAPES).map(|i| i.to_string()).collect::<Vec<_>>();
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+        let game = Game::new(&names).unwrap();
+        let decoder = GuideDecoder::shapes(&game);
+        let lines = guide(&game, &decoder, 1000);
+        let analysis = analyse_decoding(&game, &decoder, &lines).unwrap();
+        let best = &analysis.best;
+        assert!(best.total >= analysis.breakdown.total());
+        assert!(analysis.worst.total <= analysis.breakdown.total());
+        // Every shape is still played by exactly one letter.
+        assert!(game.shapes().all(|shape| best
+            .mapping
+            .iter()
+            .filter(|(_, r)| *r == Response::Shape(shape))
+            .count()
+            == 1));
+    }
+
+    #[test]
+    fn analyse_guide_test() {
+        let game = Game::rock_paper_scissors();
+        let lines = INPUT.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+        let analysis = analyse_guide(&game, &lines).unwrap();
+
+        let shapes = &analysis.shape_reading;
+        assert_eq!(
+            Breakdown {
+                wins: 1,
+                draws: 1,
+                losses: 1,
+                shape_points: 6,
+                outcome_points: 9
+            },
+            shapes.breakdown
+        );
+        assert_eq!(15, shapes.breakdown.total());
+        assert_eq!(Outcome::Win, shapes.rounds[0].outcome);
+        assert_eq!(
+            8,
+            shapes.rounds[0].shape_points + shapes.rounds[0].outcome_points
+        );
+        assert_eq!(24, shapes.best.total);
+        assert_eq!(
+            vec![
+                ('X', Response::Shape(SCISSORS)),
+                ('Y', Response::Shape(PAPER)),
+                ('Z', Response::Shape(ROCK))
+            ],
+            shapes.best.mapping
+        );
+        assert_eq!(6, shapes.worst.total);
+
+        let outcomes = &analysis.outcome_reading;
+        assert_eq!(12, outcomes.breakdown.total());
+        assert_eq!(18, outcomes.best.total);
+        assert_eq!(12, outcomes.worst.total);
+        assert_eq!(
+            GuideDecoder::outcomes(&game).responses,
+            outcomes.worst.mapping
+        );
+
+        let json = serde_json::to_value(&analysis).unwrap();
+        assert_eq!(24, json["shape_reading"]["best"]["total"]);
+        assert_eq!("Win", json["shape_reading"]["rounds"][0]["outcome"]);
+    }
 }
 
-const ROCK: char = 'r';
-const PAPER: char = 'p';
-const SCISSORS: char = 's';
+/// A shape as an index into the game's shapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+struct Shape(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+enum Outcome {
+    Loss,
+    Draw,
+    Win,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GameError {
+    EvenShapeCount(usize),
+    /// Fewer than 3 shapes can't be played, and more than `MAX_SHAPES` can't be written in the
+    /// guide.
+    ShapeCount(usize),
+    ShapeScoreCount {
+        expected: usize,
+        found: usize,
+    },
+    InvalidLine(String),
+}
+
+/// The most shapes a game can have, the guide gives each one a letter.
+const MAX_SHAPES: usize = 25;
+
+/// A cyclic hand game with an odd number of shapes. Each shape beats the shapes an odd number
+/// of places before it in the cycle, so every shape beats exactly half of the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Game {
+    names: Vec<String>,
+    shape_scores: Vec<u32>,
+    /// Indexed by `Outcome`.
+    outcome_scores: [u32; 3],
+}
+
+impl Game {
+    /// Shapes score 1, 2, 3... in order and a loss, draw or win scores 0, 3 or 6.
+    fn new(names: &[&str]) -> Result<Self, GameError> {
+        if !(3..=MAX_SHAPES).contains(&names.len()) {
+            return Err(GameError::ShapeCount(names.len()));
+        }
+        if names.len().is_multiple_of(2) {
+            return Err(GameError::EvenShapeCount(names.len()));
+        }
+        Ok(Self {
+            names: names.iter().map(|n| n.to_string()).collect(),
+            shape_scores: (1..=names.len() as u32).collect(),
+            outcome_scores: [0, 3, 6],
+        })
+    }
+
+    fn rock_paper_scissors() -> Self {
+        Self::new(&["rock", "paper", "scissors"]).unwrap()
+    }
+
+    fn rock_paper_scissors_lizard_spock() -> Self {
+        Self::new(&["rock", "paper", "scissors", "spock", "lizard"]).unwrap()
+    }
+
+    fn with_shape_scores(self, shape_scores: Vec<u32>) -> Result<Self, GameError> {
+        if shape_scores.len() != self.names.len() {
+            return Err(GameError::ShapeScoreCount {
+                expected: self.names.len(),
+                found: shape_scores.len(),
+            });
+        }
+        Ok(Self {
+            shape_scores,
+            ..self
+        })
+    }
+
+    fn with_outcome_scores(self, loss: u32, draw: u32, win: u32) -> Self {
+        Self {
+            outcome_scores: [loss, draw, win],
+            ..self
+        }
+    }
+
+    fn shapes(&self) -> impl Iterator<Item = Shape> {
+        (0..self.names.len()).map(Shape)
+    }
+
+    /// The outcome for `me`.
+    fn outcome(&self, opponent: Shape, me: Shape) -> Outcome {
+        let n = self.names.len();
+        match (me.0 + n - opponent.0) % n {
+            0 => Outcome::Draw,
+            d if d % 2 == 1 => Outcome::Win,
+            _ => Outcome::Loss,
+        }
+    }
+
+    /// The shape giving the outcome against the opponent. When more than one shape would, the
+    /// highest scoring is chosen.
+    fn shape_for(&self, opponent: Shape, outcome: Outcome) -> Shape {
+        self.shapes()
+            .filter(|s| self.outcome(opponent, *s) == outcome)
+            .min_by_key(|s| std::cmp::Reverse(self.shape_scores[s.0]))
+            .unwrap()
+    }
+
+    fn score(&self, opponent: Shape, me: Shape) -> u32 {
+        self.outcome_scores[self.outcome(opponent, me) as usize] + self.shape_scores[me.0]
+    }
+}
+
+/// How to respond according to the second column of the guide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+enum Response {
+    Shape(Shape),
+    Outcome(Outcome),
+}
+
+/// Reads lines of the strategy guide. The opponent's shapes are `A`, `B`, `C`... in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GuideDecoder {
+    opponent: Vec<char>,
+    responses: Vec<(char, Response)>,
+}
+
+impl GuideDecoder {
+    /// The second column is the shape to play, using the last letters of the alphabet so three
+    /// shapes are `X`, `Y` and `Z`.
+    fn shapes(game: &Game) -> Self {
+        let n = game.names.len() as u8;
+        Self {
+            opponent: (b'A'..b'A' + n).map(char::from).collect(),
+            responses: (b'Z' + 1 - n..=b'Z')
+                .map(char::from)
+                .zip(game.shapes().map(Response::Shape))
+                .collect(),
+        }
+    }
+
+    /// The second column is the outcome needed, `X` to lose, `Y` to draw and `Z` to win.
+    fn outcomes(game: &Game) -> Self {
+        let n = game.names.len() as u8;
+        Self {
+            opponent: (b'A'..b'A' + n).map(char::from).collect(),
+            responses: vec![
+                ('X', Response::Outcome(Outcome::Loss)),
+                ('Y', Response::Outcome(Outcome::Draw)),
+                ('Z', Response::Outcome(Outcome::Win)),
+            ],
+        }
+    }
+
+    fn decode(&self, line: &str) -> Result<(Shape, Response), GameError> {
+        use nom::character::complete::{anychar, char};
+        use nom::combinator::eof;
+        use nom::sequence::tuple;
+
+        let invalid = || GameError::InvalidLine(line.to_string());
+        let (_, (opponent, _, response, _)) = tuple((anychar, char(' '), anychar, eof))(line)
+            .map_err(|_: nom::Err<nom::error::Error<&str>>| invalid())?;
+        let opponent = self
+            .opponent
+            .iter()
+            .position(|c| *c == opponent)
+            .ok_or_else(invalid)?;
+        let response = self
+            .responses
+            .iter()
+            .find(|(c, _)| *c == response)
+            .ok_or_else(invalid)?;
+        Ok((Shape(opponent), response.1))
+    }
+}
+
+/// The shapes played by the opponent and by you.
+fn play(game: &Game, round: (Shape, Response)) -> (Shape, Shape) {
+    match round {
+        (opponent, Response::Shape(me)) => (opponent, me),
+        (opponent, Response::Outcome(outcome)) => (opponent, game.shape_for(opponent, outcome)),
+    }
+}
+
+/// Points scored by a set of rounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+struct Breakdown {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    shape_points: u32,
+    outcome_points: u32,
+}
+
+impl Breakdown {
+    fn total(&self) -> u32 {
+        self.shape_points + self.outcome_points
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+struct RoundScore {
+    opponent: Shape,
+    me: Shape,
+    outcome: Outcome,
+    shape_points: u32,
+    outcome_points: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct MappingTotal {
+    mapping: Vec<(char, Response)>,
+    total: u32,
+}
+
+/// How a guide plays out under one reading of the second column. `best` and `worst` are over
+/// every way of assigning the second column's letters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DecodingAnalysis {
+    rounds: Vec<RoundScore>,
+    breakdown: Breakdown,
+    best: MappingTotal,
+    worst: MappingTotal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct GuideAnalysis {
+    shapes: Vec<String>,
+    shape_reading: DecodingAnalysis,
+    outcome_reading: DecodingAnalysis,
+}
+
+/// The way of giving each row its own column with the least total cost, as the column for
+/// each row, for a square table of costs. Takes O(n³) time, using the Hungarian method.
+fn min_cost_assignment(cost: &[Vec<i64>]) -> Vec<usize> {
+    let n = cost.len();
+    // Row and column potentials, and the row matched to each column, all from 1 with column
+    // 0 standing for the row being added.
+    let mut row_potential = vec![0i64; n + 1];
+    let mut column_potential = vec![0i64; n + 1];
+    let mut matched = vec![0usize; n + 1];
+    let mut previous = vec![0usize; n + 1];
+    for row in 1..=n {
+        matched[0] = row;
+        let mut column = 0;
+        let mut slack = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+        while matched[column] != 0 {
+            used[column] = true;
+            let current = matched[column];
+            let mut delta = i64::MAX;
+            let mut next = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced =
+                    cost[current - 1][j - 1] - row_potential[current] - column_potential[j];
+                if reduced < slack[j] {
+                    slack[j] = reduced;
+                    previous[j] = column;
+                }
+                if slack[j] < delta {
+                    delta = slack[j];
+                    next = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    row_potential[matched[j]] += delta;
+                    column_potential[j] -= delta;
+                } else {
+                    slack[j] -= delta;
+                }
+            }
+            column = next;
+        }
+        while column != 0 {
+            let before = previous[column];
+            matched[column] = matched[before];
+            column = before;
+        }
+    }
+    let mut assignment = vec![0; n];
+    for j in 1..=n {
+        assignment[matched[j] - 1] = j - 1;
+    }
+    assignment
+}
+
+fn score_rounds(
+    game: &Game,
+    decoder: &GuideDecoder,
+    lines: &[String],
+) -> Result<Vec<RoundScore>, GameError> {
+    lines
+        .iter()
+        .map(|line| {
+            let (opponent, me) = play(game, decoder.decode(line)?);
+            let score = rock_paper_scissors_score_game(game, (opponent, me));
+            let shape_points = game.shape_scores[me.0];
+            Ok(RoundScore {
+                opponent,
+                me,
+                outcome: game.outcome(opponent, me),
+                shape_points,
+                outcome_points: score - shape_points,
+            })
+        })
+        .collect()
+}
+
+fn analyse_decoding(
+    game: &Game,
+    decoder: &GuideDecoder,
+    lines: &[String],
+) -> Result<DecodingAnalysis, GameError> {
+    let rounds = score_rounds(game, decoder, lines)?;
+    let mut breakdown = Breakdown::default();
+    for round in rounds.iter() {
+        match round.outcome {
+            Outcome::Win => breakdown.wins += 1,
+            Outcome::Draw => breakdown.draws += 1,
+            Outcome::Loss => breakdown.losses += 1,
+        }
+        breakdown.shape_points += round.shape_points;
+        breakdown.outcome_points += round.outcome_points;
+    }
+
+    // What each letter of the second column would score over the whole guide if it meant each
+    // of the responses, so that every way of assigning them can be totalled without trying it.
+    let responses = &decoder.responses;
+    let mut scores = vec![vec![0i64; responses.len()]; responses.len()];
+    for line in lines {
+        let (opponent, response) = decoder.decode(line)?;
+        let letter = responses.iter().position(|(_, r)| *r == response).unwrap();
+        for (score, (_, meaning)) in scores[letter].iter_mut().zip(responses) {
+            *score += rock_paper_scissors_score_game(game, play(game, (opponent, *meaning))) as i64;
+        }
+    }
+    // Of equal totals, the one keeping the most letters as they are wins.
+    let scale = responses.len() as i64 + 1;
+    let keep = |letter: usize, meaning: usize| (letter == meaning) as i64;
+    let mapping_total = |sign: i64| {
+        let cost = scores
+            .iter()
+            .enumerate()
+            .map(|(letter, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(meaning, score)| -sign * (score * scale) - keep(letter, meaning))
+                    .collect()
+            })
+            .collect::<Vec<_>>();
+        let assignment = min_cost_assignment(&cost);
+        MappingTotal {
+            mapping: assignment
+                .iter()
+                .enumerate()
+                .map(|(letter, &meaning)| (responses[letter].0, responses[meaning].1))
+                .collect(),
+            total: assignment
+                .iter()
+                .enumerate()
+                .map(|(letter, &meaning)| scores[letter][meaning] as u32)
+                .sum(),
+        }
+    };
+    let best = mapping_total(1);
+    let worst = mapping_total(-1);
+
+    Ok(DecodingAnalysis {
+        rounds,
+        breakdown,
+        best,
+        worst,
+    })
+}
+
+fn analyse_guide(game: &Game, lines: &[String]) -> Result<GuideAnalysis, GameError> {
+    Ok(GuideAnalysis {
+        shapes: game.names.clone(),
+        shape_reading: analyse_decoding(game, &GuideDecoder::shapes(game), lines)?,
+        outcome_reading: analyse_decoding(game, &GuideDecoder::outcomes(game), lines)?,
+    })
+}
+
+fn rock_paper_scissors() -> &'static Game {
+    static GAME: OnceLock<Game> = OnceLock::new();
+    GAME.get_or_init(Game::rock_paper_scissors)
+}
 
 fn rock_paper_scissors_play_game_part1(line_input: &str) -> u32 {
-    rock_paper_scissors_score_game(rock_paper_scissors_get_characters(line_input))
+    let rules = rock_paper_scissors();
+    rock_paper_scissors_score_game(rules, rock_paper_scissors_get_characters(line_input))
 }
 fn rock_paper_scissors_play_game_part2(line_input: &str) -> u32 {
-    let (elf_chose, score) = rock_paper_scissors_get_characters_encoded(line_input);
-    let you_choose = calculate_rock_paper_scissors(elf_chose, score);
-    rock_paper_scissors_score_game((elf_chose, you_choose))
-}
-
-fn rock_paper_scissors_score_game(game: (char, char)) -> u32 {
-    rock_paper_scissors_win_lose_score(game) + rock_paper_scissors_score(game.1)
-}
-
-fn rock_paper_scissors_score(rps: char) -> u32 {
-    match rps {
-        ROCK => 1,
-        PAPER => 2,
-        SCISSORS => 3,
-        _ => panic!(),
-    }
-}
-
-fn rock_paper_scissors_win_lose_score(game: (char, char)) -> u32 {
-    match game {
-        (ROCK, ROCK) => 3,
-        (ROCK, PAPER) => 6,
-        (ROCK, SCISSORS) => 0,
-        (PAPER, ROCK) => 0,
-        (PAPER, PAPER) => 3,
-        (PAPER, SCISSORS) => 6,
-        (SCISSORS, ROCK) => 6,
-        (SCISSORS, PAPER) => 0,
-        (SCISSORS, SCISSORS) => 3,
-        _ => panic!(),
-    }
-}
-
-fn rock_paper_scissors_get_characters(line_input: &str) -> (char, char) {
-    use nom::character::complete::anychar;
-    use nom::character::complete::one_of;
-    let (remaining, elf_chose) =
-        one_of::<_, _, (&str, nom::error::ErrorKind)>("ABC")(line_input).unwrap();
-    let (remaining, _) = anychar::<_, (&str, nom::error::ErrorKind)>(remaining).unwrap();
-    let (_, me_chose) = one_of::<_, _, (&str, nom::error::ErrorKind)>("XYZ")(remaining).unwrap();
-    let elf_chose = match elf_chose {
-        'A' => ROCK,
-        'B' => PAPER,
-        'C' => SCISSORS,
-        _ => panic!(),
-    };
-    let me_chose = match me_chose {
-        'X' => ROCK,
-        'Y' => PAPER,
-        'Z' => SCISSORS,
-        _ => panic!(),
-    };
-    (elf_chose, me_chose)
-}
-fn rock_paper_scissors_get_characters_encoded(line_input: &str) -> (char, u32) {
-    use nom::character::complete::anychar;
-    use nom::character::complete::one_of;
-    let (remaining, elf_chose) =
-        one_of::<_, _, (&str, nom::error::ErrorKind)>("ABC")(line_input).unwrap();
-    let (remaining, _) = anychar::<_, (&str, nom::error::ErrorKind)>(remaining).unwrap();
-    let (_, me_chose) = one_of::<_, _, (&str, nom::error::ErrorKind)>("XYZ")(remaining).unwrap();
-    let elf_chose = match elf_chose {
-        'A' => ROCK,
-        'B' => PAPER,
-        'C' => SCISSORS,
-        _ => panic!(),
-    };
-    let me_chose = match me_chose {
-        'X' => 0,
-        'Y' => 3,
-        'Z' => 6,
-        _ => panic!(),
-    };
-    (elf_chose, me_chose)
-}
-
-fn calculate_rock_paper_scissors(elf_chose: char, outcome: u32) -> char {
-    match elf_chose {
-        ROCK => match outcome {
-            0 => SCISSORS,
-            3 => ROCK,
-            6 => PAPER,
-            _ => panic!(),
-        },
-        PAPER => match outcome {
-            0 => ROCK,
-            3 => PAPER,
-            6 => SCISSORS,
-            _ => panic!(),
-        },
-        SCISSORS => match outcome {
-            0 => PAPER,
-            3 => SCISSORS,
-            6 => ROCK,
-            _ => panic!(),
-        },
-        _ => panic!(),
-    }
+    let rules = rock_paper_scissors();
+    let round = GuideDecoder::outcomes(rules).decode(line_input).unwrap();
+    rock_paper_scissors_score_game(rules, play(rules, round))
+}
+
+fn rock_paper_scissors_score_game(rules: &Game, game: (Shape, Shape)) -> u32 {
+    rules.score(game.0, game.1)
+}
+
+fn rock_paper_scissors_get_characters(line_input: &str) -> (Shape, Shape) {
+    let game = rock_paper_scissors();
+    let round = GuideDecoder::shapes(game).decode(line_input).unwrap();
+    play(game, round)
 }
 
 fn main() -> std::io::Result<()> {
@@ -154,7 +692,58 @@ fn main() -> std::io::Result<()> {
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let file = File::open(&path)?;
+
+    // Analyse the guide, optionally under another game and as JSON,
+    // e.g. `day2 analyse lizard-spock shapes=1,2,3,4,5 outcomes=0,3,6 json`.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(|a| a.as_str()) == Some("analyse") {
+        let numbers = |s: &str| {
+            s.split(',')
+                .map(|n| n.parse::<u32>().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let mut game = Game::rock_paper_scissors();
+        let mut json = false;
+        for arg in args.iter().skip(1) {
+            match arg.split_once('=') {
+                None if arg == "json" => json = true,
+                None if arg == "lizard-spock" => game = Game::rock_paper_scissors_lizard_spock(),
+                Some(("shapes", scores)) => game = game.with_shape_scores(numbers(scores)).unwrap(),
+                Some(("outcomes", scores)) => {
+                    let scores = numbers(scores);
+                    game = game.with_outcome_scores(scores[0], scores[1], scores[2]);
+                }
+                _ => panic!("unknown argument {}", arg),
+            }
+        }
+        let file = File::open(path)?;
+        let lines = io::BufReader::new(file)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()?;
+        let analysis = analyse_guide(&game, &lines).unwrap();
+        if json {
+            println!("{}", serde_json::to_string_pretty(&analysis).unwrap());
+        } else {
+            for (reading, decoding) in [
+                ("shape", &analysis.shape_reading),
+                ("outcome", &analysis.outcome_reading),
+            ] {
+                println!("{} reading: {:?}", reading, decoding.breakdown);
+                println!("  total {}", decoding.breakdown.total());
+                println!(
+                    "  best {} with {:?}",
+                    decoding.best.total, decoding.best.mapping
+                );
+                println!(
+                    "  worst {} with {:?}",
+                    decoding.worst.total, decoding.worst.mapping
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let file = File::open(path)?;
     let lines = io::BufReader::new(file).lines();
     println!(
         "{}",
@@ -162,7 +751,7 @@ fn main() -> std::io::Result<()> {
             .map(|l| rock_paper_scissors_play_game_part1(&l.unwrap()))
             .sum::<u32>()
     );
-    let file = File::open(&path)?;
+    let file = File::open(path)?;
     let lines = io::BufReader::new(file).lines();
     println!(
         "{}",
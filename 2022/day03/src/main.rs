@@ -9,40 +9,51 @@ wMqvLMZHhHMvwLHjbvcjnnSBnvTQFn
 ttgJtRGJQctTZtZT
 CrZsJsPPZsGzwwsLwLmpwMDw";
 
+    /// The items a single rucksack has in both compartments.
+    fn common_in_line(line: &str) -> Result<ItemSet, RucksackError> {
+        Ok(common_in_compartments(std::iter::once(line), 2)?[0].items)
+    }
+
     #[test]
     fn rucksack_common_sum() {
-        let lines = INPUT.lines();
-        let res = lines
-            .map(|l| {
-                let mut result: [bool; 53] = [false; 53];
-                common_items_in_compartment(l, &mut result);
-                score_common_items(&result)
-            })
-            .sum();
-        assert_eq!(157u32, res);
+        let shared = common_in_compartments(INPUT.lines(), 2).unwrap();
+        assert_eq!(157u32, shared.iter().map(|s| s.items.score()).sum());
+        assert_eq!(Ok(157), calculate_compartment_value(INPUT));
+    }
+
+    #[test]
+    fn compartment_value_line_test() {
+        assert_eq!(
+            Err(RucksackError::UnevenCompartments {
+                line: 2,
+                length: 5,
+                compartments: 2
+            }),
+            calculate_compartment_value("abab\nabcda")
+        );
+        assert_eq!(
+            Err(RucksackError::InvalidItem { line: 3, item: '1' }),
+            calculate_compartment_value("abab\nabab\nab1b")
+        );
     }
 
     #[test]
     fn rucksack_common_split_value_check_line1() {
         const LINE_INPUT: &str = "vJrwpWtwJgWrhcsFMMfFFhFp";
-        let mut result: [bool; 53] = [false; 53];
-        common_items_in_compartment(LINE_INPUT, &mut result);
-        assert_eq!(16u32, score_common_items(&result));
+        assert_eq!(16u32, common_in_line(LINE_INPUT).unwrap().score());
     }
     #[test]
     fn rucksack_common_split_value_check_line2() {
         const LINE_INPUT: &str = "jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL";
-        let mut result: [bool; 53] = [false; 53];
-        common_items_in_compartment(LINE_INPUT, &mut result);
-        assert_eq!(38u32, score_common_items(&result));
+        assert_eq!(38u32, common_in_line(LINE_INPUT).unwrap().score());
     }
 
     #[test]
     fn rucksack_common_split_check() {
         const LINE_INPUT: &str = "vJrwpWtwJgWrhcsFMMfFFhFp";
         assert_eq!(
-            ("vJrwpWtwJgWr", "hcsFMMfFFhFp"),
-            items_in_compartments(LINE_INPUT)
+            Ok(vec!["vJrwpWtwJgWr", "hcsFMMfFFhFp"]),
+            split_compartments(LINE_INPUT, 2)
         );
     }
 
@@ -52,7 +63,7 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
 jqHRNqRjqzjGDLGLrsFMfFZSrLrFZsSL
 PmmdzqPrVvPwwTWBwg";
         let final_result = calculate_badge_value(GROUP_LINE_INPUT);
-        assert_eq!(18, final_result);
+        assert_eq!(Ok(18), final_result);
     }
     #[test]
     fn rucksack_get_badges_check2() {
@@ -60,131 +71,292 @@ PmmdzqPrVvPwwTWBwg";
 ttgJtRGJQctTZtZT
 CrZsJsPPZsGzwwsLwLmpwMDw";
         let final_result = calculate_badge_value(GROUP_LINE_INPUT);
-        assert_eq!(52, final_result);
+        assert_eq!(Ok(52), final_result);
+    }
+
+    #[test]
+    fn rucksack_item_set_check() {
+        let items = ItemSet::from_items("aAzZa").unwrap();
+        assert_eq!(4, items.len());
+        assert_eq!(vec!['a', 'z', 'A', 'Z'], items.items().collect::<Vec<_>>());
+        assert_eq!(1 + 26 + 27 + 52, items.score());
+        let other = ItemSet::from_items("bzZ").unwrap();
+        assert_eq!(
+            vec!['z', 'Z'],
+            items.intersection(other).items().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(RucksackError::InvalidItem { line: 1, item: '1' }),
+            ItemSet::from_items("ab1")
+        );
+    }
+
+    #[test]
+    fn rucksack_errors_check() {
+        assert_eq!(
+            Err(RucksackError::UnevenCompartments {
+                line: 1,
+                length: 5,
+                compartments: 2
+            }),
+            common_in_line("abcda")
+        );
+        assert_eq!(
+            Err(RucksackError::InvalidItem { line: 4, item: '!' }),
+            common_in_compartments(INPUT.replace("wMq", "wM!").lines(), 2)
+        );
+        assert_eq!(
+            Err(RucksackError::InvalidItem {
+                line: 1, item: 'é'
+            }),
+            common_in_line("aébc")
+        );
+        assert_eq!(
+            Err(RucksackError::IncompleteGroup {
+                lines: 6,
+                group_size: 4
+            }),
+            common_in_groups(INPUT.lines(), 4)
+        );
+    }
+
+    #[test]
+    fn rucksack_shared_check() {
+        let shared = common_in_groups(INPUT.lines(), 3).unwrap();
+        assert_eq!(vec![1, 2, 3], shared[0].lines);
+        assert_eq!(vec!['r'], shared[0].items.items().collect::<Vec<_>>());
+        assert_eq!(vec![4, 5, 6], shared[1].lines);
+        assert_eq!(vec!['Z'], shared[1].items.items().collect::<Vec<_>>());
+
+        let shared = common_in_groups(INPUT.lines(), 2).unwrap();
+        assert_eq!(3, shared.len());
+        assert_eq!(
+            vec!['G', 'J', 'Z'],
+            shared[2].items.items().collect::<Vec<_>>()
+        );
+
+        let shared = common_in_compartments(["abcaxyazy"].into_iter(), 3).unwrap();
+        assert_eq!(vec![1], shared[0].lines);
+        assert_eq!(vec!['a'], shared[0].items.items().collect::<Vec<_>>());
     }
 }
 
-fn calculate_badge_value(lines: &str) -> u32 {
-    let mut count1 = [0u32; 53];
-    let mut count2 = [0u32; 53];
-    let mut count3 = [0u32; 53];
-    let mut result = [false; 53];
-    let lines = lines.lines();
-    let lines = lines.into_iter().collect::<Vec<&str>>();
-    items_in_counted(lines[0], &mut count1);
-    items_in_counted(lines[1], &mut count2);
-    items_in_counted(lines[2], &mut count3);
-
-    for i in 0..53 {
-        result[i] = count1[i] > 0 && count2[i] > 0 && count3[i] > 0;
-    }
-    result
-        .iter()
-        .enumerate()
-        .map(|(i, v)| if *v { i as u32 } else { 0u32 })
-        .sum::<u32>()
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RucksackError {
+    /// Only `a` to `z` and `A` to `Z` are items.
+    InvalidItem { line: usize, item: char },
+    UnevenCompartments {
+        line: usize,
+        length: usize,
+        compartments: usize,
+    },
+    /// The number of lines isn't a multiple of the group size.
+    IncompleteGroup { lines: usize, group_size: usize },
 }
 
-const fn value_map() -> [usize; 128] {
-    let mut retval = [0usize; 128];
-    let mut i = 0;
-    while i < 128 {
-        if i >= 65 && i < 91 {
-            retval[i] = i - 64 + 26;
-        } else if i >= 97 && i < 123 {
-            retval[i] = i - 96;
+impl RucksackError {
+    fn at_line(self, line: usize) -> Self {
+        match self {
+            RucksackError::InvalidItem { item, .. } => RucksackError::InvalidItem { line, item },
+            RucksackError::UnevenCompartments {
+                length,
+                compartments,
+                ..
+            } => RucksackError::UnevenCompartments {
+                line,
+                length,
+                compartments,
+            },
+            e => e,
         }
-        i += 1;
     }
-    retval
 }
 
-fn score_common_items(result: &[bool; 53]) -> u32 {
-    result
-        .iter()
-        .enumerate()
-        .map(|(i, v)| if *v { i as u32 } else { 0u32 })
-        .sum::<u32>()
+/// A set of items, bit `n` is set for the item with priority `n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    /// Every item, the starting point for finding what a number of sets have in common.
+    const ALL: ItemSet = ItemSet(((1u64 << 52) - 1) << 1);
+
+    fn from_items(items: &str) -> Result<Self, RucksackError> {
+        items.chars().try_fold(ItemSet::default(), |set, item| {
+            let priority = priority(item).ok_or(RucksackError::InvalidItem { line: 1, item })?;
+            Ok(ItemSet(set.0 | 1 << priority))
+        })
+    }
+
+    fn intersection(self, other: ItemSet) -> ItemSet {
+        ItemSet(self.0 & other.0)
+    }
+
+    fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    fn priorities(self) -> impl Iterator<Item = u32> {
+        (1..=52).filter(move |p| self.0 & (1 << p) != 0)
+    }
+
+    fn items(self) -> impl Iterator<Item = char> {
+        self.priorities().map(item)
+    }
+
+    /// The sum of the priorities.
+    fn score(self) -> u32 {
+        self.priorities().sum()
+    }
 }
 
-fn common_items_in_compartment(line: &str, result: &mut [bool; 53]) {
-    let mut c1r = [0u32; 53];
-    let mut c2r = [0u32; 53];
-    let (c1, c2) = items_in_compartments(line);
+fn priority(item: char) -> Option<u32> {
+    match item {
+        'a'..='z' => Some(item as u32 - 'a' as u32 + 1),
+        'A'..='Z' => Some(item as u32 - 'A' as u32 + 27),
+        _ => None,
+    }
+}
 
-    items_in_counted(c1, &mut c1r);
-    items_in_counted(c2, &mut c2r);
+fn item(priority: u32) -> char {
+    match priority {
+        1..=26 => char::from_u32('a' as u32 + priority - 1).unwrap(),
+        _ => char::from_u32('A' as u32 + priority - 27).unwrap(),
+    }
+}
+
+/// The items found in every one of the numbered lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Shared {
+    lines: Vec<usize>,
+    items: ItemSet,
+}
 
-    for i in 0..53 {
-        result[i] = (c1r[i] > 0) && (c2r[i] > 0);
+fn split_compartments(line: &str, compartments: usize) -> Result<Vec<&str>, RucksackError> {
+    // Checked first so the line can be split by bytes.
+    ItemSet::from_items(line)?;
+    if compartments == 0 || !line.len().is_multiple_of(compartments) {
+        return Err(RucksackError::UnevenCompartments {
+            line: 1,
+            length: line.len(),
+            compartments,
+        });
     }
+    let size = line.len() / compartments;
+    Ok((0..compartments)
+        .map(|i| &line[i * size..(i + 1) * size])
+        .collect())
 }
 
-#[inline]
-fn items_in_counted(items: &str, count: &mut [u32; 53]) {
-    let bitems = items.as_bytes();
+fn common_items(sets: impl IntoIterator<Item = ItemSet>) -> ItemSet {
+    sets.into_iter().fold(ItemSet::ALL, ItemSet::intersection)
+}
 
-    bitems.iter().for_each(|b| {
-        let i = MAP[(*b as usize)];
-        count[i as usize] += 1;
-    });
+/// The items each line has in all of its compartments. Lines are numbered from 1.
+fn common_in_compartments<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    compartments: usize,
+) -> Result<Vec<Shared>, RucksackError> {
+    lines
+        .enumerate()
+        .map(|(i, line)| {
+            let sets: Vec<ItemSet> = split_compartments(line, compartments)
+                .and_then(|c| c.into_iter().map(ItemSet::from_items).collect())
+                .map_err(|e| e.at_line(i + 1))?;
+            Ok(Shared {
+                lines: vec![i + 1],
+                items: common_items(sets),
+            })
+        })
+        .collect()
 }
 
-#[inline]
-fn items_in_compartments(line: &str) -> (&str, &str) {
-    let middle = line.len() / 2;
-    line.split_at(middle)
+/// The items common to every rucksack in each consecutive group of lines.
+fn common_in_groups<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    group_size: usize,
+) -> Result<Vec<Shared>, RucksackError> {
+    let sets = lines
+        .enumerate()
+        .map(|(i, line)| ItemSet::from_items(line).map_err(|e| e.at_line(i + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if group_size == 0 || !sets.len().is_multiple_of(group_size) {
+        return Err(RucksackError::IncompleteGroup {
+            lines: sets.len(),
+            group_size,
+        });
+    }
+    Ok(sets
+        .chunks(group_size)
+        .enumerate()
+        .map(|(g, group)| Shared {
+            lines: (g * group_size + 1..=(g + 1) * group_size).collect(),
+            items: common_items(group.iter().copied()),
+        })
+        .collect())
 }
 
-const MAP: [usize; 128] = value_map();
+/// The badges of every group of three.
+fn calculate_badge_value(lines: &str) -> Result<u32, RucksackError> {
+    let groups = common_in_groups(lines.lines(), 3)?;
+    Ok(groups.iter().map(|g| g.items.score()).sum())
+}
+
+/// The items shared by the two compartments of every rucksack.
+fn calculate_compartment_value(lines: &str) -> Result<u32, RucksackError> {
+    let shared = common_in_compartments(lines.lines(), 2)?;
+    Ok(shared.iter().map(|s| s.items.score()).sum())
+}
 
 fn main() -> std::io::Result<()> {
     use std::fs::File;
-    use std::io;
     use std::io::prelude::*;
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    // let file = File::open(&path)?;
-    // let mut input = String::new();
-    // file.read_to_string(&mut input)?;
-
-    let file = File::open(&path)?;
-
-    let lines = io::BufReader::new(file).lines();
-    let res = lines
-        .map(|l| {
-            let mut result: [bool; 53] = [false; 53];
-            common_items_in_compartment(&l.unwrap(), &mut result);
-            score_common_items(&result)
-        })
-        .sum::<u32>();
-
-    println!("{}", res);
-
-    let file = File::open(&path)?;
-    let lines = io::BufReader::new(file)
-        .lines()
-        .map(|l| l.unwrap())
-        .collect::<Vec<String>>();
-    let len = lines.len();
-    let group_len = len / 3;
-
-    let mut res = 0u32;
-
-    for i in 0..group_len {
-        let i1 = i * 3;
-        let i2 = i1 + 1;
-        let i3 = i1 + 2;
-        let mut group = String::new();
-        group.push_str(&lines[i1]);
-        group.push_str("\n");
-        group.push_str(&lines[i2]);
-        group.push_str("\n");
-        group.push_str(&lines[i3]);
-        res += calculate_badge_value(&group);
-    }
-
-    println!("{}", res);
+    let mut file = File::open(path)?;
+    let mut input = String::new();
+    file.read_to_string(&mut input)?;
+
+    // Show what is shared for other layouts, e.g. `day3 compartments=3 groups=4`.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        let mut compartments = 2;
+        let mut groups = 3;
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("compartments", n)) => compartments = n.parse().unwrap(),
+                Some(("groups", n)) => groups = n.parse().unwrap(),
+                _ => panic!("unknown argument {}", arg),
+            }
+        }
+        let print = |shared: Result<Vec<Shared>, RucksackError>| match shared {
+            Ok(shared) => {
+                for s in shared.iter() {
+                    println!(
+                        "{:?} {} {}",
+                        s.lines,
+                        s.items.len(),
+                        s.items.items().collect::<String>()
+                    );
+                }
+                let total = shared.iter().map(|s| s.items.score()).sum::<u32>();
+                println!("{}", total);
+            }
+            Err(e) => println!("{:?}", e),
+        };
+        print(common_in_compartments(input.lines(), compartments));
+        print(common_in_groups(input.lines(), groups));
+        return Ok(());
+    }
+
+    match calculate_compartment_value(&input) {
+        Ok(res) => println!("{}", res),
+        Err(e) => println!("{:?}", e),
+    }
+
+    match calculate_badge_value(&input) {
+        Ok(res) => println!("{}", res),
+        Err(e) => println!("{:?}", e),
+    }
     Ok(())
 }
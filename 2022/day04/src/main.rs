@@ -5,8 +5,8 @@ mod test {
     #[test]
     fn check_for_within_pairs() {
         const LINE: &str = "2-4,6-8";
-        let pairs = get_pairs(LINE);
-        assert_eq!(false, within_either(&pairs[0], &pairs[1]))
+        let pairs = get_pairs(LINE).unwrap();
+        assert!(!within_either(&pairs[0], &pairs[1]))
     }
 
     #[test]
@@ -30,38 +30,279 @@ mod test {
         run_overlap_test("6-6,4-6", true);
         run_overlap_test("2-6,4-8", true);
     }
+    #[test]
+    fn check_pair_algebra() {
+        let a = Pair::new(2, 6).unwrap();
+        let b = Pair::new(4, 8).unwrap();
+        assert_eq!(None, Pair::new(5, 4));
+        assert_eq!(5, a.len());
+        assert!(a.contains(2) && a.contains(6) && !a.contains(7));
+        assert_eq!(Pair::new(4, 6), a.intersection(&b));
+        assert_eq!(None, a.intersection(&Pair::new(7, 9).unwrap()));
+        assert_eq!(Pair::new(2, 8), a.union(&b));
+        assert_eq!(Pair::new(2, 9), a.union(&Pair::new(7, 9).unwrap()));
+        assert_eq!(None, a.union(&Pair::new(8, 9).unwrap()));
+        assert_eq!(vec![Pair::new(2, 3).unwrap()], a.difference(&b).ranges());
+        assert_eq!(
+            vec![Pair::new(2, 2).unwrap(), Pair::new(6, 6).unwrap()],
+            a.difference(&Pair::new(3, 5).unwrap()).ranges()
+        );
+        assert!(a.difference(&Pair::new(1, 9).unwrap()).ranges().is_empty());
+    }
+
+    #[test]
+    fn check_interval_set() {
+        let set = [(10, 12), (2, 4), (6, 7), (5, 5), (20, 30), (11, 21)]
+            .into_iter()
+            .map(|(s, e)| Pair::new(s, e).unwrap())
+            .collect::<IntervalSet>();
+        assert_eq!(
+            vec![Pair::new(2, 7).unwrap(), Pair::new(10, 30).unwrap()],
+            set.ranges()
+        );
+        assert_eq!(27, set.len());
+        assert!(set.contains(7) && !set.contains(8) && set.contains(10));
+        assert_eq!(
+            vec![Pair::new(1, 1).unwrap(), Pair::new(8, 9).unwrap()],
+            set.gaps(&Pair::new(1, 12).unwrap()).ranges()
+        );
+    }
+
+    #[test]
+    fn check_coverage() {
+        let pairs = get_all_pairs("2-4,6-8\n2-3,4-5\n5-7,7-9\n2-8,3-7\n6-6,4-6\n2-6,4-8")
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        assert!(uncovered(&pairs).ranges().is_empty());
+        assert_eq!(
+            vec![Pair::new(12, 12).unwrap()],
+            uncovered(&[Pair::new(10, 11).unwrap(), Pair::new(13, 14).unwrap()]).ranges()
+        );
+        assert_eq!(
+            vec![(Pair::new(2, 2).unwrap(), 4), (Pair::new(3, 3).unwrap(), 5)],
+            coverage(&pairs)[..2]
+        );
+        assert_eq!(
+            vec![Pair::new(6, 6).unwrap()],
+            covered_by_more_than(&pairs, 7).ranges()
+        );
+    }
+
+    #[test]
+    fn check_pair_errors() {
+        assert_eq!(
+            Err(PairError::Malformed {
+                line: 2,
+                text: "3-4,x".to_string()
+            }),
+            get_all_pairs("1-2,3-4\n3-4,x")
+        );
+        assert_eq!(
+            Err(PairError::Malformed {
+                line: 1,
+                text: "1-2,3-4,".to_string()
+            }),
+            get_pairs("1-2,3-4,")
+        );
+        assert_eq!(
+            Err(PairError::Reversed {
+                line: 1,
+                start: 4,
+                end: 3
+            }),
+            get_pairs("1-2,4-3")
+        );
+    }
+
     fn run_within_test(line: &str, expected_result: bool) {
-        let pairs = get_pairs(line);
+        let pairs = get_pairs(line).unwrap();
         assert_eq!(expected_result, within_either(&pairs[0], &pairs[1]))
     }
 
     fn run_overlap_test(line: &str, expected_result: bool) {
-        let pairs = get_pairs(line);
+        let pairs = get_pairs(line).unwrap();
         assert_eq!(expected_result, overlaps_either(&pairs[0], &pairs[1]));
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PairError {
+    /// `line` is numbered from 1.
+    Malformed { line: usize, text: String },
+    /// The assignment ends before it starts.
+    Reversed { line: usize, start: u32, end: u32 },
+}
+
+impl PairError {
+    fn at_line(self, line: usize) -> Self {
+        match self {
+            PairError::Malformed { text, .. } => PairError::Malformed { line, text },
+            PairError::Reversed { start, end, .. } => PairError::Reversed { line, start, end },
+        }
+    }
+}
+
+/// An inclusive range of sections, `start` is never after `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 struct Pair {
     start: u32,
     end: u32,
 }
 
 impl Pair {
+    fn new(start: u32, end: u32) -> Option<Pair> {
+        (start <= end).then_some(Pair { start, end })
+    }
+
+    /// The number of sections.
+    fn len(&self) -> u64 {
+        (self.end - self.start) as u64 + 1
+    }
+
+    fn contains(&self, section: u32) -> bool {
+        self.start <= section && section <= self.end
+    }
+
     fn within(&self, other: &Pair) -> bool {
-        other.start >= self.start
-            && other.start <= self.end
-            && other.end >= self.start
-            && other.end <= self.end
-            && other.end >= other.start
-            && self.end >= self.start
+        self.contains(other.start) && self.contains(other.end)
     }
 
     fn overlaps(&self, other: &Pair) -> bool {
-        ((other.start >= self.start && other.start <= self.end)
-            || (other.end <= self.end && other.end >= self.start))
-            && other.end >= other.start
-            && self.end >= self.start
+        self.intersection(other).is_some()
+    }
+
+    fn intersection(&self, other: &Pair) -> Option<Pair> {
+        Pair::new(self.start.max(other.start), self.end.min(other.end))
+    }
+
+    /// The two ranges as one, if they overlap or are next to each other.
+    fn union(&self, other: &Pair) -> Option<Pair> {
+        let (first, second) = if self <= other {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        (second.start as u64 <= first.end as u64 + 1)
+            .then(|| Pair::new(first.start, first.end.max(second.end)).unwrap())
+    }
+
+    /// The sections not in `other`, up to two ranges.
+    fn difference(&self, other: &Pair) -> IntervalSet {
+        let mut set = IntervalSet::default();
+        if !self.overlaps(other) {
+            set.insert(*self);
+            return set;
+        }
+        if other.start > self.start {
+            set.insert(Pair::new(self.start, other.start - 1).unwrap());
+        }
+        if other.end < self.end {
+            set.insert(Pair::new(other.end + 1, self.end).unwrap());
+        }
+        set
+    }
+}
+
+/// A set of sections, kept as sorted ranges that neither overlap nor touch.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct IntervalSet {
+    ranges: Vec<Pair>,
+}
+
+impl IntervalSet {
+    /// Adds the range, merging it with any range it overlaps or touches.
+    fn insert(&mut self, pair: Pair) {
+        let first = self
+            .ranges
+            .partition_point(|r| (r.end as u64 + 1) < pair.start as u64);
+        let mut merged = pair;
+        let mut last = first;
+        while let Some(union) = self.ranges.get(last).and_then(|r| r.union(&merged)) {
+            merged = union;
+            last += 1;
+        }
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    fn ranges(&self) -> &[Pair] {
+        &self.ranges
+    }
+
+    /// The number of sections.
+    fn len(&self) -> u64 {
+        self.ranges.iter().map(Pair::len).sum()
+    }
+
+    fn contains(&self, section: u32) -> bool {
+        let i = self.ranges.partition_point(|r| r.end < section);
+        self.ranges.get(i).is_some_and(|r| r.contains(section))
+    }
+
+    /// The sections of `bounds` that aren't in the set.
+    fn gaps(&self, bounds: &Pair) -> IntervalSet {
+        self.ranges
+            .iter()
+            .fold(IntervalSet::from_iter([*bounds]), |remaining, r| {
+                remaining
+                    .ranges
+                    .iter()
+                    .flat_map(|g| g.difference(r).ranges)
+                    .collect()
+            })
+    }
+}
+
+impl FromIterator<Pair> for IntervalSet {
+    fn from_iter<T: IntoIterator<Item = Pair>>(iter: T) -> Self {
+        let mut set = IntervalSet::default();
+        for pair in iter {
+            set.insert(pair);
+        }
+        set
+    }
+}
+
+/// How many of the assignments cover each section, as ranges with the same count. Sections
+/// nobody covers are left out.
+fn coverage(pairs: &[Pair]) -> Vec<(Pair, usize)> {
+    // The count changes at each start and just after each end.
+    let mut changes = std::collections::BTreeMap::<u64, i64>::new();
+    for pair in pairs {
+        *changes.entry(pair.start as u64).or_default() += 1;
+        *changes.entry(pair.end as u64 + 1).or_default() -= 1;
+    }
+    let mut count = 0;
+    let mut ranges = Vec::new();
+    let mut changes = changes.into_iter().peekable();
+    while let Some((section, change)) = changes.next() {
+        count += change;
+        if let Some((next, _)) = changes.peek() {
+            if count > 0 {
+                let pair = Pair::new(section as u32, (next - 1) as u32).unwrap();
+                ranges.push((pair, count as usize));
+            }
+        }
+    }
+    ranges
+}
+
+/// The sections covered by more than `k` of the assignments.
+fn covered_by_more_than(pairs: &[Pair], k: usize) -> IntervalSet {
+    coverage(pairs)
+        .into_iter()
+        .filter(|(_, count)| *count > k)
+        .map(|(pair, _)| pair)
+        .collect()
+}
+
+/// The sections between the first and last assigned that nobody covers.
+fn uncovered(pairs: &[Pair]) -> IntervalSet {
+    let covered = pairs.iter().copied().collect::<IntervalSet>();
+    match (covered.ranges().first(), covered.ranges().last()) {
+        (Some(first), Some(last)) => covered.gaps(&Pair::new(first.start, last.end).unwrap()),
+        _ => IntervalSet::default(),
     }
 }
 
@@ -73,63 +314,97 @@ fn within_either(pair1: &Pair, pair2: &Pair) -> bool {
     pair1.within(pair2) || pair2.within(pair1)
 }
 
-fn parse(input: &str) -> nom::IResult<&str, (&str, &str, &str, &str)> {
-    use nom::character::complete::{char, digit0};
-    let (rest, digit1) = digit0(input)?;
+fn parse(input: &str) -> nom::IResult<&str, (u32, u32, u32, u32)> {
+    use nom::character::complete::{char, u32};
+    let (rest, digit1) = u32(input)?;
     let (rest, _) = char('-')(rest)?;
-    let (rest, digit2) = digit0(rest)?;
+    let (rest, digit2) = u32(rest)?;
     let (rest, _) = char(',')(rest)?;
-    let (rest, digit3) = digit0(rest)?;
+    let (rest, digit3) = u32(rest)?;
     let (rest, _) = char('-')(rest)?;
-    let (rest, digit4) = digit0(rest)?;
+    let (rest, digit4) = u32(rest)?;
     Ok((rest, (digit1, digit2, digit3, digit4)))
 }
-// fn read_pairs(line: &str, pairs: &[Pairs; 2]) {
-fn get_pairs(line: &str) -> [Pair; 2] {
-    let output = parse(line).unwrap();
-    let pair1 = Pair {
-        start: output.1 .0.parse::<u32>().unwrap(),
-        end: output.1 .1.parse::<u32>().unwrap(),
+
+/// A single line's pairs, errors are at line 1 until `get_all_pairs` numbers them.
+fn get_pairs(line: &str) -> Result<[Pair; 2], PairError> {
+    let malformed = || PairError::Malformed {
+        line: 1,
+        text: line.to_string(),
     };
-    let pair2 = Pair {
-        start: output.1 .2.parse::<u32>().unwrap(),
-        end: output.1 .3.parse::<u32>().unwrap(),
+    let (start1, end1, start2, end2) = match parse(line) {
+        Ok(("", output)) => output,
+        _ => return Err(malformed()),
     };
-    // println!("{:?}", output);
-    // println!("{:?}", pair1);
-    // println!("{:?}", pair2);
-    [pair1, pair2]
+    let pair = |start, end| {
+        Pair::new(start, end).ok_or(PairError::Reversed {
+            line: 1,
+            start,
+            end,
+        })
+    };
+    Ok([pair(start1, end1)?, pair(start2, end2)?])
+}
+
+/// Every line's pairs, with errors numbered by line.
+fn get_all_pairs(input: &str) -> Result<Vec<[Pair; 2]>, PairError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| get_pairs(line).map_err(|e| e.at_line(i + 1)))
+        .collect()
 }
 
 fn main() -> std::io::Result<()> {
     use std::io::prelude::*;
     let path = std::path::Path::new("input.txt");
-    let file = std::fs::File::open(&path)?;
-    let lines = std::io::BufReader::new(file).lines();
-    let within = lines
-        .map(|l| {
-            let pairs = get_pairs(&l.unwrap());
-            if within_either(&pairs[0], &pairs[1]) {
-                1
-            } else {
-                0
-            }
-        })
-        .sum::<u32>();
+    let mut file = std::fs::File::open(path)?;
+    let mut input = String::new();
+    file.read_to_string(&mut input)?;
+    let pairs = match get_all_pairs(&input) {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            println!("{:?}", e);
+            return Ok(());
+        }
+    };
+
+    // `coverage [k] [section]` shows the sections nobody covers and those covered by more than
+    // k elves, or only whether the one section is covered by more than k.
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("coverage") {
+        let k = args.next().map_or(1, |k| k.parse().unwrap());
+        let assignments = pairs.iter().flatten().copied().collect::<Vec<_>>();
+        let print = |name: &str, set: IntervalSet| {
+            let ranges = set
+                .ranges()
+                .iter()
+                .map(|r| format!("{}-{}", r.start, r.end))
+                .collect::<Vec<_>>();
+            println!("{} ({} sections): {}", name, set.len(), ranges.join(","));
+        };
+        let crowded = covered_by_more_than(&assignments, k);
+        if let Some(section) = args.next() {
+            let section = section.parse().unwrap();
+            println!("{}: {}", section, crowded.contains(section));
+            return Ok(());
+        }
+        print("uncovered", uncovered(&assignments));
+        print(&format!("covered by more than {}", k), crowded);
+        let busiest = coverage(&assignments).into_iter().max_by_key(|(_, c)| *c);
+        if let Some((pair, count)) = busiest {
+            println!("busiest {}-{} by {}", pair.start, pair.end, count);
+        }
+        return Ok(());
+    }
+
+    let within = pairs.iter().filter(|p| within_either(&p[0], &p[1])).count();
     println!("{}", within);
 
-    let file = std::fs::File::open(&path)?;
-    let lines = std::io::BufReader::new(file).lines();
-    let overlaps = lines
-        .map(|l| {
-            let pairs = get_pairs(&l.unwrap());
-            if overlaps_either(&pairs[0], &pairs[1]) {
-                1
-            } else {
-                0
-            }
-        })
-        .sum::<u32>();
+    let overlaps = pairs
+        .iter()
+        .filter(|p| overlaps_either(&p[0], &p[1]))
+        .count();
     println!("{}", overlaps);
     Ok(())
 }
@@ -2,6 +2,16 @@
 mod test {
     use super::*;
 
+    const EXAMPLE: &str = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 
+
+move 1 from 2 to 1
+move 3 from 1 to 3
+move 2 from 2 to 1
+move 1 from 1 to 2";
+
     #[test]
     fn parse_line_check_outputs() {
         const INPUT: &str = "move 1 from 2 to 1";
@@ -20,12 +30,9 @@ move 3 from 1 to 3
 move 2 from 2 to 1
 move 1 from 1 to 2";
 
-        let split = INPUT.split("\n\n").collect::<Vec<&str>>();
-        let (_, mut stacks) = parse_crates(split[0]).unwrap();
-        split[1].lines().for_each(|l| {
-            let (_, result) = parse_moves(l).unwrap();
-            move_between_stacks_single(&mut stacks, result.0, result.1, result.2);
-        });
+        let (mut yard, moves) = parse_input(INPUT).unwrap();
+        solve(&mut yard, &moves, &OneAtATime).unwrap();
+        let stacks = &yard.stacks;
         assert_eq!('C', stacks[0][stacks[0].len() - 1]);
         assert_eq!('M', stacks[1][stacks[1].len() - 1]);
         assert_eq!('Z', stacks[2][stacks[2].len() - 1]);
@@ -43,16 +50,172 @@ move 3 from 1 to 3
 move 2 from 2 to 1
 move 1 from 1 to 2";
 
-        let split = INPUT.split("\n\n").collect::<Vec<&str>>();
-        let (_, mut stacks) = parse_crates(split[0]).unwrap();
-        split[1].lines().for_each(|l| {
-            let (_, result) = parse_moves(l).unwrap();
-            move_between_stacks_multiple(&mut stacks, result.0, result.1, result.2);
-        });
+        let (mut yard, moves) = parse_input(INPUT).unwrap();
+        solve(&mut yard, &moves, &AllAtOnce).unwrap();
+        let stacks = &yard.stacks;
         assert_eq!('M', stacks[0][stacks[0].len() - 1]);
         assert_eq!('C', stacks[1][stacks[1].len() - 1]);
         assert_eq!('D', stacks[2][stacks[2].len() - 1]);
     }
+    #[test]
+    fn crate_yard_undo_redo_test() {
+        let (mut yard, moves) = parse_input(EXAMPLE).unwrap();
+        let start = yard.clone();
+        assert_eq!("CMZ", solve(&mut yard, &moves, &OneAtATime).unwrap().tops);
+        let end = yard.stacks.clone();
+        assert_eq!(Some(moves[3]), yard.undo());
+        assert_eq!("MZ", yard.tops());
+        while yard.undo().is_some() {}
+        assert_eq!(start.stacks, yard.stacks);
+        assert_eq!(Some(moves[0]), yard.redo());
+        while yard.redo().is_some() {}
+        assert_eq!(end, yard.stacks);
+
+        yard.undo();
+        yard.apply(&moves[3], &AllAtOnce).unwrap();
+        assert_eq!(None, yard.redo());
+    }
+
+    #[test]
+    fn crate_yard_same_stack_test() {
+        let cranes: [&dyn Crane; 3] = [&OneAtATime, &AllAtOnce, &Bounded::new(2).unwrap()];
+        for crane in cranes {
+            let (mut yard, _) = parse_input(EXAMPLE).unwrap();
+            let start = yard.stacks.clone();
+            let mv = Move {
+                count: 3,
+                from: 0,
+                to: 0,
+                line: 6,
+            };
+            assert_eq!(
+                Err(CrateError::NotEnoughCrates {
+                    line: 6,
+                    stack: 1,
+                    wanted: 3,
+                    available: 2
+                }),
+                yard.apply(&mv, crane)
+            );
+            yard.stacks[0].push('X');
+            let three = yard.stacks.clone();
+            assert_eq!(Ok(0), yard.apply(&mv, crane));
+            assert_eq!(three, yard.stacks);
+            assert_eq!(Some(mv), yard.undo());
+            assert_eq!(three, yard.stacks);
+            assert_eq!(Some(mv), yard.redo());
+            assert_eq!(three, yard.stacks);
+            yard.undo();
+            yard.stacks[0].pop();
+            assert_eq!(start, yard.stacks);
+        }
+    }
+
+    #[test]
+    fn crate_yard_errors_test() {
+        let (mut yard, _) = parse_input(EXAMPLE).unwrap();
+        let mv = Move {
+            count: 4,
+            from: 0,
+            to: 1,
+            line: 6,
+        };
+        assert_eq!(
+            Err(CrateError::NotEnoughCrates {
+                line: 6,
+                stack: 1,
+                wanted: 4,
+                available: 2
+            }),
+            yard.apply(&mv, &OneAtATime)
+        );
+        assert_eq!(
+            Err(CrateError::NoSuchStack { line: 6, stack: 4 }),
+            yard.apply(&Move { to: 3, ..mv }, &OneAtATime)
+        );
+        assert_eq!(2, yard.stacks[0].len());
+
+        let (mut yard, moves) =
+            parse_input(&EXAMPLE.replace("move 2 from 2", "move 4 from 2")).unwrap();
+        assert_eq!(
+            Err(CrateError::NotEnoughCrates {
+                line: 8,
+                stack: 2,
+                wanted: 4,
+                available: 2
+            }),
+            solve(&mut yard, &moves, &AllAtOnce)
+        );
+        assert_eq!(
+            Err(CrateError::Malformed {
+                line: 7,
+                text: "move 3 from 0 to 3".to_string()
+            }),
+            parse_input(&EXAMPLE.replace("from 1 to 3", "from 0 to 3"))
+        );
+        assert_eq!(
+            Err(CrateError::Malformed {
+                line: 1,
+                text: "    [D] [X]".to_string()
+            }),
+            parse_input(&EXAMPLE.replace("    [D]    ", "    [D] [X]"))
+        );
+    }
+
+    #[test]
+    fn crate_yard_render_round_trip_test() {
+        let (diagram, _) = EXAMPLE.split_once("\n\n").unwrap();
+        let mut yard = CrateYard::from_diagram(diagram).unwrap();
+        assert_eq!(diagram, yard.render());
+        let (_, moves) = parse_input(EXAMPLE).unwrap();
+        solve(&mut yard, &moves, &AllAtOnce).unwrap();
+        let rendered = yard.render();
+        assert_eq!(
+            "        [D]\n        [N]\n        [Z]\n[M] [C] [P]\n 1   2   3 ",
+            rendered
+        );
+        assert_eq!(
+            yard.stacks,
+            CrateYard::from_diagram(&rendered).unwrap().stacks
+        );
+    }
+
+    #[test]
+    fn crane_models_test() {
+        let taken = ['A', 'B', 'C', 'D', 'E'];
+        assert_eq!((vec!['E', 'D', 'C', 'B', 'A'], 5), OneAtATime.lift(&taken));
+        assert_eq!((taken.to_vec(), 1), AllAtOnce.lift(&taken));
+        assert_eq!(
+            (vec!['D', 'E', 'B', 'C', 'A'], 3),
+            Bounded::new(2).unwrap().lift(&taken)
+        );
+        assert_eq!((vec![], 0), AllAtOnce.lift(&[]));
+        assert_eq!(None, Bounded::new(0));
+
+        let (yard, moves) = parse_input(EXAMPLE).unwrap();
+        let report = |crane: &dyn Crane| solve(&mut yard.clone(), &moves, crane).unwrap();
+        assert_eq!(
+            Report {
+                tops: "CMZ".to_string(),
+                lifts: 7,
+                max_height: 4
+            },
+            report(&OneAtATime)
+        );
+        assert_eq!(report(&OneAtATime), report(&Bounded::new(1).unwrap()));
+        assert_eq!(
+            Report {
+                tops: "MCD".to_string(),
+                lifts: 4,
+                max_height: 4
+            },
+            report(&AllAtOnce)
+        );
+        assert_eq!("MCD", report(&Bounded::new(3).unwrap()).tops);
+        assert_eq!(5, report(&Bounded::new(2).unwrap()).lifts);
+        assert!(crane_named("0").is_none());
+    }
+
     fn parse_line_check_given_input_with_outputs(
         input: &str,
         number_to_move: u32,
@@ -66,109 +229,343 @@ move 1 from 1 to 2";
     }
 }
 
-fn parse_crate_title(input: &str) -> nom::IResult<&str, Option<char>> {
-    use nom::character::complete::anychar;
-    use nom::sequence::tuple;
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CrateError {
+    /// `line` is numbered from 1 across the whole input.
+    Malformed { line: usize, text: String },
+    /// Stacks are numbered from 1 as in the diagram.
+    NoSuchStack { line: usize, stack: usize },
+    NotEnoughCrates {
+        line: usize,
+        stack: usize,
+        wanted: usize,
+        available: usize,
+    },
+}
 
-    let (rest, result) = tuple((anychar, anychar, anychar))(input)?;
-    let result = if result.1.is_numeric() {
-        Some(result.1)
-    } else {
-        None
-    };
-    Ok((rest, result))
+/// Move `count` crates, `from` and `to` are indexes into the stacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Move {
+    count: usize,
+    from: usize,
+    to: usize,
+    line: usize,
 }
-fn parse_crate(input: &str) -> nom::IResult<&str, Option<char>> {
-    use nom::character::complete::anychar;
-    use nom::sequence::tuple;
-
-    let (rest, result) = tuple((anychar, anychar, anychar))(input)?;
-    let result = if result.1.is_alphabetic() {
-        Some(result.1)
-    } else {
-        None
-    };
-    Ok((rest, result))
+
+/// How a crane carries crates from one stack to another.
+trait Crane {
+    /// Given the crates taken, bottom first, returns them bottom first as they end up on the
+    /// other stack and the number of lifts it took.
+    fn lift(&self, taken: &[char]) -> (Vec<char>, usize);
+}
+
+/// The CrateMover 9000, which reverses the crates it moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OneAtATime;
+
+impl Crane for OneAtATime {
+    fn lift(&self, taken: &[char]) -> (Vec<char>, usize) {
+        (taken.iter().rev().copied().collect(), taken.len())
+    }
+}
+
+/// The CrateMover 9001, which keeps the crates in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AllAtOnce;
+
+impl Crane for AllAtOnce {
+    fn lift(&self, taken: &[char]) -> (Vec<char>, usize) {
+        (taken.to_vec(), usize::from(!taken.is_empty()))
+    }
+}
+
+/// Lifts at most `capacity` crates at a time off the top, keeping each lift in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bounded {
+    capacity: usize,
+}
+
+impl Bounded {
+    fn new(capacity: usize) -> Option<Self> {
+        (capacity > 0).then_some(Bounded { capacity })
+    }
+}
+
+impl Crane for Bounded {
+    fn lift(&self, taken: &[char]) -> (Vec<char>, usize) {
+        let lifts = taken.rchunks(self.capacity);
+        let count = lifts.len();
+        (lifts.flatten().copied().collect(), count)
+    }
+}
+
+/// A crane by name, `9000`, `9001` or the number of crates it can lift at a time.
+fn crane_named(name: &str) -> Option<Box<dyn Crane>> {
+    match name {
+        "9000" => Some(Box::new(OneAtATime)),
+        "9001" => Some(Box::new(AllAtOnce)),
+        _ => Some(Box::new(Bounded::new(name.parse().ok()?)?)),
+    }
+}
+
+/// A move that has been made, with the crates it took and how it left them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Applied {
+    mv: Move,
+    /// Bottom first, as they were on the `from` stack.
+    taken: Vec<char>,
+    /// Bottom first, as they are on the `to` stack.
+    placed: Vec<char>,
 }
 
-fn parse_crates(input: &str) -> nom::IResult<&str, Vec<Vec<char>>> {
+/// The stacks of crates, with the moves made so far so they can be undone and redone.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct CrateYard {
+    /// Bottom crate first.
+    stacks: Vec<Vec<char>>,
+    done: Vec<Applied>,
+    undone: Vec<Applied>,
+}
+
+impl CrateYard {
+    fn new(stacks: Vec<Vec<char>>) -> Self {
+        Self {
+            stacks,
+            ..Default::default()
+        }
+    }
+
+    fn from_diagram(diagram: &str) -> Result<Self, CrateError> {
+        parse_crates(diagram).map(CrateYard::new)
+    }
+
+    /// The crate on top of each stack, empty stacks are skipped.
+    fn tops(&self) -> String {
+        self.stacks.iter().filter_map(|s| s.last()).collect()
+    }
+
+    /// Checks the move can be made without changing anything.
+    fn validate(&self, mv: &Move) -> Result<(), CrateError> {
+        for stack in [mv.from, mv.to] {
+            if stack >= self.stacks.len() {
+                return Err(CrateError::NoSuchStack {
+                    line: mv.line,
+                    stack: stack + 1,
+                });
+            }
+        }
+        let available = self.stacks[mv.from].len();
+        if available < mv.count {
+            return Err(CrateError::NotEnoughCrates {
+                line: mv.line,
+                stack: mv.from + 1,
+                wanted: mv.count,
+                available,
+            });
+        }
+        Ok(())
+    }
+
+    /// Makes the move, returning the number of lifts. Nothing undone can be redone after this.
+    /// Moving crates onto the stack they are on leaves them where they are, for no lifts.
+    fn apply(&mut self, mv: &Move, crane: &dyn Crane) -> Result<usize, CrateError> {
+        self.validate(mv)?;
+        let from = &mut self.stacks[mv.from];
+        let taken = from.split_off(from.len() - mv.count);
+        let (placed, lifts) = if mv.from == mv.to {
+            (taken.clone(), 0)
+        } else {
+            crane.lift(&taken)
+        };
+        self.stacks[mv.to].extend_from_slice(&placed);
+        self.done.push(Applied {
+            mv: *mv,
+            taken,
+            placed,
+        });
+        self.undone.clear();
+        Ok(lifts)
+    }
+
+    /// Puts back the last move made, returning it.
+    fn undo(&mut self) -> Option<Move> {
+        let applied = self.done.pop()?;
+        let to = &mut self.stacks[applied.mv.to];
+        to.truncate(to.len() - applied.placed.len());
+        self.stacks[applied.mv.from].extend_from_slice(&applied.taken);
+        let mv = applied.mv;
+        self.undone.push(applied);
+        Some(mv)
+    }
+
+    /// Makes the last undone move again, returning it.
+    fn redo(&mut self) -> Option<Move> {
+        let applied = self.undone.pop()?;
+        let from = &mut self.stacks[applied.mv.from];
+        from.truncate(from.len() - applied.taken.len());
+        self.stacks[applied.mv.to].extend_from_slice(&applied.placed);
+        let mv = applied.mv;
+        self.done.push(applied);
+        Some(mv)
+    }
+
+    /// The stacks drawn the way `parse_crates` reads them.
+    fn render(&self) -> String {
+        let height = self.stacks.iter().map(Vec::len).max().unwrap_or(0);
+        let mut rows = (0..height)
+            .rev()
+            .map(|level| {
+                self.stacks
+                    .iter()
+                    .map(|s| {
+                        s.get(level)
+                            .map_or("   ".to_string(), |c| format!("[{}]", c))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>();
+        rows.push(
+            (1..=self.stacks.len())
+                .map(|i| format!(" {} ", i))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        rows.join("\n")
+    }
+}
+
+fn parse_crate_title(input: &str) -> nom::IResult<&str, usize> {
+    use nom::character::complete::{char, u32};
+    use nom::sequence::delimited;
+
+    let (rest, result) = delimited(char(' '), u32, char(' '))(input)?;
+    Ok((rest, result as usize))
+}
+
+fn parse_crate(input: &str) -> nom::IResult<&str, Option<char>> {
     use nom::branch::alt;
     use nom::bytes::complete::tag;
-    use nom::character::complete::{alpha0, digit0, satisfy};
-    use nom::combinator::value;
-    use nom::multi::many0;
-    use nom::multi::separated_list0;
-    let mut retval = Vec::<Vec<char>>::new();
+    use nom::character::complete::{char, satisfy};
+    use nom::combinator::{map, value};
+    use nom::sequence::delimited;
+
+    alt((
+        delimited(
+            char('['),
+            map(satisfy(|c| c.is_alphabetic()), Some),
+            char(']'),
+        ),
+        value(None, tag("   ")),
+    ))(input)
+}
 
-    let lines = input.lines().rev().collect::<Vec<&str>>();
+/// The stacks in the diagram, bottom crate first.
+fn parse_crates(input: &str) -> Result<Vec<Vec<char>>, CrateError> {
+    use nom::bytes::complete::tag;
+    use nom::combinator::all_consuming;
+    use nom::multi::separated_list1;
 
-    let (def_line, rest_lines) = lines.split_at(1);
+    let lines = input.lines().collect::<Vec<&str>>();
+    let malformed = |i: usize| CrateError::Malformed {
+        line: i + 1,
+        text: lines[i].to_string(),
+    };
+    let (title, rows) = lines.split_last().ok_or(CrateError::Malformed {
+        line: 1,
+        text: String::new(),
+    })?;
 
-    let (_, result) = separated_list0(tag(" "), parse_crate_title)(def_line[0])?;
+    let (_, numbers) = all_consuming(separated_list1(tag(" "), parse_crate_title))(title)
+        .map_err(|_| malformed(rows.len()))?;
+    if numbers.iter().enumerate().any(|(i, n)| *n != i + 1) {
+        return Err(malformed(rows.len()));
+    }
+    let mut retval = vec![Vec::<char>::new(); numbers.len()];
 
-    result.iter().for_each(|v| {
-        if v.is_some() {
-            retval.push(Vec::<char>::new());
+    for (i, l) in rows.iter().enumerate().rev() {
+        let (_, result) =
+            all_consuming(separated_list1(tag(" "), parse_crate))(l).map_err(|_| malformed(i))?;
+        if result.len() > retval.len() {
+            return Err(malformed(i));
         }
-    });
-
-    let mut rest = "";
-    for l in rest_lines.iter() {
-        let l = *l;
-        let (rest_update, result) = separated_list0(tag(" "), parse_crate)(l)?;
-        result.iter().enumerate().for_each(|(i, v)| {
-            if let Some(c) = v {
-                retval[i].push(*c);
+        for (stack, c) in result.iter().enumerate() {
+            match c {
+                // A crate has to be sitting on the one below it.
+                Some(c) if retval[stack].len() == rows.len() - 1 - i => retval[stack].push(*c),
+                Some(_) => return Err(malformed(i)),
+                None => {}
             }
-        });
-        rest = rest_update;
+        }
     }
 
-    Ok((rest, retval))
+    Ok(retval)
 }
+
 fn parse_moves(input: &str) -> nom::IResult<&str, (u32, usize, usize)> {
     use nom::bytes::complete::tag;
-    use nom::character::complete::digit0;
+    use nom::character::complete::u32;
+    use nom::combinator::verify;
+    let stack = || verify(u32, |n| *n > 0);
     let (rest, _) = tag("move ")(input)?;
-    let (rest, digit1) = digit0(rest)?;
+    let (rest, digit1) = u32(rest)?;
     let (rest, _) = tag(" from ")(rest)?;
-    let (rest, digit2) = digit0(rest)?;
+    let (rest, digit2) = stack()(rest)?;
     let (rest, _) = tag(" to ")(rest)?;
-    let (rest, digit3) = digit0(rest)?;
-    Ok((
-        rest,
-        (
-            digit1.parse::<u32>().unwrap(),
-            digit2.parse::<usize>().unwrap() - 1,
-            digit3.parse::<usize>().unwrap() - 1,
-        ),
-    ))
+    let (rest, digit3) = stack()(rest)?;
+    Ok((rest, (digit1, digit2 as usize - 1, digit3 as usize - 1)))
 }
 
-fn move_between_stacks_single(
-    stack: &mut Vec<Vec<char>>,
-    number_to_move: u32,
-    from: usize,
-    to: usize,
-) {
-    for _ in 0..number_to_move {
-        let c = stack[from].pop().unwrap();
-        stack[to].push(c);
-    }
+/// Reads the diagram and the moves that follow it.
+fn parse_input(input: &str) -> Result<(CrateYard, Vec<Move>), CrateError> {
+    let (diagram, moves) = input.split_once("\n\n").ok_or(CrateError::Malformed {
+        line: input.lines().count() + 1,
+        text: String::new(),
+    })?;
+    let yard = CrateYard::from_diagram(diagram)?;
+    // The moves start after the diagram and the blank line.
+    let first = diagram.lines().count() + 2;
+    let moves = moves
+        .lines()
+        .enumerate()
+        .map(|(i, l)| match parse_moves(l) {
+            Ok(("", (count, from, to))) => Ok(Move {
+                count: count as usize,
+                from,
+                to,
+                line: first + i,
+            }),
+            _ => Err(CrateError::Malformed {
+                line: first + i,
+                text: l.to_string(),
+            }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((yard, moves))
 }
 
-fn move_between_stacks_multiple(
-    stack: &mut Vec<Vec<char>>,
-    number_to_move: u32,
-    from: usize,
-    to: usize,
-) {
-    let mut temp = Vec::<char>::with_capacity(number_to_move as usize);
-    for _ in 0..number_to_move {
-        let c = stack[from].pop().unwrap();
-        temp.push(c);
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Report {
+    /// The crates left on top.
+    tops: String,
+    lifts: usize,
+    /// The most crates in any one stack at any point, including before the first move.
+    max_height: usize,
+}
+
+/// Makes every move with the crane.
+fn solve(yard: &mut CrateYard, moves: &[Move], crane: &dyn Crane) -> Result<Report, CrateError> {
+    let mut lifts = 0;
+    let mut max_height = yard.stacks.iter().map(Vec::len).max().unwrap_or(0);
+    for mv in moves {
+        lifts += yard.apply(mv, crane)?;
+        max_height = max_height.max(yard.stacks[mv.to].len());
     }
-    temp.iter().rev().for_each(|c| stack[to].push(*c));
+    Ok(Report {
+        tops: yard.tops(),
+        lifts,
+        max_height,
+    })
 }
 
 fn main() -> std::io::Result<()> {
@@ -177,52 +574,55 @@ fn main() -> std::io::Result<()> {
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path)?;
-    let mut input = String::new();
-    file.read_to_string(&mut input)?;
-    let split = input.split("\n\n").collect::<Vec<&str>>();
-    let (_, mut stacks) = parse_crates(split[0]).unwrap();
-    split[1].lines().for_each(|l| {
-        let (_, result) = parse_moves(l).unwrap();
-        move_between_stacks_single(&mut stacks, result.0, result.1, result.2);
-    });
-
-    let mut output = String::with_capacity(stacks.len());
-    for i in stacks.iter() {
-        let c = i[i.len() - 1];
-        // println!("{:?}", c);
-        output.push(c);
-    }
-    println!("{}", output);
-
-    //     const INPUT: &str = "    [D]
-    // [N] [C]
-    // [Z] [M] [P]
-    //  1   2   3
-
-    // move 1 from 2 to 1
-    // move 3 from 1 to 3
-    // move 2 from 2 to 1
-    // move 1 from 1 to 2";
-    //     let split = INPUT.split("\n\n").collect::<Vec<&str>>();
-    let path = Path::new("input.txt");
-    let mut file = File::open(&path)?;
+    let mut file = File::open(path)?;
     let mut input = String::new();
     file.read_to_string(&mut input)?;
-    let split = input.split("\n\n").collect::<Vec<&str>>();
-    let (_, mut stacks) = parse_crates(split[0]).unwrap();
-    split[1].lines().for_each(|l| {
-        let (_, result) = parse_moves(l).unwrap();
-        move_between_stacks_multiple(&mut stacks, result.0, result.1, result.2);
-    });
+    let (yard, moves) = match parse_input(&input) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("{:?}", e);
+            return Ok(());
+        }
+    };
 
-    let mut output = String::with_capacity(stacks.len());
-    for i in stacks.iter() {
-        let c = i[i.len() - 1];
-        // println!("{:?}", c);
-        output.push(c);
+    // `at N [crane]` draws the stacks as they were after the first N moves, and `cranes
+    // [crane...]` compares cranes. A crane is `9000`, `9001` or how many crates it can lift.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("at") => {
+            let n = args[1].parse::<usize>().unwrap();
+            let crane = crane_named(args.get(2).map_or("9000", String::as_str)).unwrap();
+            let mut yard = yard;
+            if let Err(e) = solve(&mut yard, &moves, crane.as_ref()) {
+                println!("{:?}", e);
+            }
+            while yard.done.len() > n {
+                yard.undo();
+            }
+            println!("{}", yard.render());
+            while yard.redo().is_some() {}
+            println!("{}", yard.tops());
+        }
+        Some("cranes") => {
+            for name in args[1..].iter() {
+                let crane = crane_named(name).unwrap();
+                match solve(&mut yard.clone(), &moves, crane.as_ref()) {
+                    Ok(report) => println!(
+                        "{}: {} in {} lifts, {} high at most",
+                        name, report.tops, report.lifts, report.max_height
+                    ),
+                    Err(e) => println!("{}: {:?}", name, e),
+                }
+            }
+        }
+        _ => {
+            for crane in [&OneAtATime as &dyn Crane, &AllAtOnce] {
+                match solve(&mut yard.clone(), &moves, crane) {
+                    Ok(report) => println!("{}", report.tops),
+                    Err(e) => println!("{:?}", e),
+                }
+            }
+        }
     }
-    println!("{}", output);
-
     Ok(())
 }
@@ -1,93 +1,416 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Read};
+
 #[cfg(test)]
 mod test {
     use super::*;
+
+    fn find_first_marker_index1(line: &str) -> usize {
+        find_first_marker_index(line.as_bytes(), 4)
+            .unwrap()
+            .expect("no start-of-packet marker")
+    }
+    fn find_first_marker_index2(line: &str) -> usize {
+        find_first_marker_index(line.as_bytes(), 14)
+            .unwrap()
+            .expect("no start-of-message marker")
+    }
+
     #[test]
     fn find_first_marker_test1() {
         const LINE1: &str = "bvwbjplbgvbhsrlpgdmjqwftvncz";
 
-        assert_eq!(5, find_first_marker_index1(&LINE1));
+        assert_eq!(5, find_first_marker_index1(LINE1));
     }
     #[test]
     fn find_first_marker_test2() {
         const LINE2: &str = "nppdvjthqldpwncqszvftbrmjlhg";
 
-        assert_eq!(6, find_first_marker_index1(&LINE2));
+        assert_eq!(6, find_first_marker_index1(LINE2));
     }
     #[test]
     fn find_first_marker_test3() {
         const LINE3: &str = "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg";
 
-        assert_eq!(10, find_first_marker_index1(&LINE3));
+        assert_eq!(10, find_first_marker_index1(LINE3));
     }
     #[test]
     fn find_first_marker_test4() {
         const LINE4: &str = "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw";
 
-        assert_eq!(11, find_first_marker_index1(&LINE4));
+        assert_eq!(11, find_first_marker_index1(LINE4));
+    }
+
+    #[test]
+    fn all_markers_test() {
+        const LINE: &str = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+        let markers = Markers::new(LINE.as_bytes(), 4)
+            .unwrap()
+            .collect::<std::io::Result<Vec<_>>>()
+            .unwrap();
+        let bytes = LINE.as_bytes();
+        let expected = (4..=bytes.len())
+            .filter(|end| {
+                let window = &bytes[end - 4..*end];
+                (1..4).all(|i| !window[..i].contains(&window[i]))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(7, markers[0]);
+        assert_eq!(expected, markers);
+
+        assert_eq!(
+            Some(1),
+            find_first_marker_index(LINE.as_bytes(), 1).unwrap()
+        );
+        assert_eq!(
+            Some(2),
+            find_first_marker_index(LINE.as_bytes(), 2).unwrap()
+        );
+        assert_eq!(
+            None,
+            find_first_marker_index("abcabc".as_bytes(), 4).unwrap()
+        );
+        assert_eq!(None, find_first_marker_index(LINE.as_bytes(), 257).unwrap());
+
+        assert!(Markers::new(LINE.as_bytes(), 0).is_none());
+        assert!(Framer::new(LINE.as_bytes(), 0).is_none());
+        assert_eq!(
+            std::io::ErrorKind::InvalidInput,
+            find_first_marker_index(LINE.as_bytes(), 0)
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    #[test]
+    fn markers_from_stream_test() {
+        const LINE: &str = "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg";
+        // One byte per read, as a slow stream might deliver it.
+        let reader = LINE
+            .as_bytes()
+            .chunks(1)
+            .fold(Box::new(std::io::empty()) as Box<dyn Read>, |r, c| {
+                Box::new(r.chain(c))
+            });
+        let mut markers = Markers::new(std::io::BufReader::new(reader), 14).unwrap();
+        assert_eq!(29, markers.next().unwrap().unwrap());
+        assert_eq!(30, markers.next().unwrap().unwrap());
+    }
+
+    #[test]
+    fn framer_test() {
+        let frames = Framer::new("aaabcdqqqrstzzzyxwmm".as_bytes(), 4)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Err(FrameError::Unframed { start: 0, end: 2 }),
+                Ok(Frame {
+                    start: 6,
+                    end: 8,
+                    payload: b"qq".to_vec()
+                }),
+                Ok(Frame {
+                    start: 12,
+                    end: 14,
+                    payload: b"zz".to_vec()
+                }),
+                Err(FrameError::Truncated { start: 18, end: 20 }),
+            ],
+            frames
+        );
+
+        let frames = Framer::new("abcdefgh".as_bytes(), 4)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![Ok(Frame {
+                start: 4,
+                end: 4,
+                payload: vec![]
+            })],
+            frames
+        );
+        assert_eq!(
+            vec![Err(FrameError::Unframed { start: 0, end: 6 })],
+            Framer::new("abcabc".as_bytes(), 4)
+                .unwrap()
+                .collect::<Vec<_>>()
+        );
+        assert!(Framer::new("".as_bytes(), 4).unwrap().next().is_none());
     }
 
     #[test]
     fn find_first_marker2_test1() {
         const LINE1: &str = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
 
-        assert_eq!(19, find_first_marker_index2(&LINE1));
+        assert_eq!(19, find_first_marker_index2(LINE1));
     }
     #[test]
     fn find_first_marker2_test2() {
         const LINE2: &str = "bvwbjplbgvbhsrlpgdmjqwftvncz";
 
-        assert_eq!(23, find_first_marker_index2(&LINE2));
+        assert_eq!(23, find_first_marker_index2(LINE2));
     }
     #[test]
     fn find_first_marker2_test3() {
         const LINE2: &str = "nppdvjthqldpwncqszvftbrmjlhg";
 
-        assert_eq!(23, find_first_marker_index2(&LINE2));
+        assert_eq!(23, find_first_marker_index2(LINE2));
     }
     #[test]
     fn find_first_marker2_test4() {
         const LINE3: &str = "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg";
 
-        assert_eq!(29, find_first_marker_index2(&LINE3));
+        assert_eq!(29, find_first_marker_index2(LINE3));
     }
     #[test]
     fn find_first_marker2_test5() {
         const LINE4: &str = "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw";
 
-        assert_eq!(26, find_first_marker_index2(&LINE4));
+        assert_eq!(26, find_first_marker_index2(LINE4));
     }
 }
-fn find_first_marker_index(line: &str, window: usize) -> usize {
-    let char_array = line.chars().collect::<Vec<char>>();
 
-    for (i, w) in char_array.windows(window).enumerate() {
-        let mut hash_map = std::collections::HashMap::<char, char>::new();
-        for j in w.iter() {
-            hash_map.insert(*j, *j);
+/// Every position in a stream where the `window` bytes before it are all different, found in
+/// one pass by keeping a count of each byte in the window.
+#[derive(Debug)]
+struct Markers<R> {
+    bytes: std::io::Bytes<R>,
+    window: usize,
+    recent: VecDeque<u8>,
+    counts: [usize; 256],
+    /// How many byte values are in the window more than once.
+    duplicates: usize,
+    position: usize,
+}
+
+impl<R: BufRead> Markers<R> {
+    /// `None` if `window` is 0, as a marker needs at least one byte.
+    fn new(reader: R, window: usize) -> Option<Self> {
+        (window > 0).then(|| Self {
+            bytes: reader.bytes(),
+            window,
+            recent: VecDeque::with_capacity(window),
+            counts: [0; 256],
+            duplicates: 0,
+            position: 0,
+        })
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.recent.len() == self.window {
+            let old = self.recent.pop_front().unwrap() as usize;
+            self.counts[old] -= 1;
+            if self.counts[old] == 1 {
+                self.duplicates -= 1;
+            }
+        }
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 2 {
+            self.duplicates += 1;
         }
-        if hash_map.len() == window {
-            return i + window;
+        self.recent.push_back(byte);
+        self.position += 1;
+    }
+}
+
+impl<R: BufRead> Iterator for Markers<R> {
+    /// The number of bytes read when the marker is complete.
+    type Item = std::io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(byte) = self.bytes.next() {
+            match byte {
+                Ok(byte) => self.push(byte),
+                Err(e) => return Some(Err(e)),
+            }
+            if self.recent.len() == self.window && self.duplicates == 0 {
+                return Some(Ok(self.position));
+            }
         }
+        None
     }
-    panic!();
 }
 
-fn find_first_marker_index1(line: &str) -> usize {
-    find_first_marker_index(line, 4)
+/// An `InvalidInput` error if `window` is 0.
+fn find_first_marker_index(reader: impl BufRead, window: usize) -> std::io::Result<Option<usize>> {
+    Markers::new(reader, window)
+        .ok_or(std::io::ErrorKind::InvalidInput)?
+        .next()
+        .transpose()
 }
-fn find_first_marker_index2(line: &str) -> usize {
-    find_first_marker_index(line, 14)
+
+/// Keeps a copy of every byte read through it.
+struct Recorded<'a, R> {
+    reader: &'a mut R,
+    seen: &'a mut Vec<u8>,
 }
 
-fn main() {
+impl<R: BufRead> Read for Recorded<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Recorded<'_, R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.reader.fill_buf() {
+            self.seen.extend_from_slice(&buf[..amt]);
+        }
+        self.reader.consume(amt);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FrameError {
+    /// Bytes before the first marker, or a stream without any marker.
+    Unframed {
+        start: usize,
+        end: usize,
+    },
+    /// The stream ended before the marker closing the last frame.
+    Truncated {
+        start: usize,
+        end: usize,
+    },
+    Io(std::io::ErrorKind),
+}
+
+/// The payload between two markers, `start` and `end` are its offsets in the stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    start: usize,
+    end: usize,
+    payload: Vec<u8>,
+}
+
+/// Splits a stream into the payloads between successive markers of length `window`. Each
+/// marker closes one frame and opens the next.
+struct Framer<R> {
+    reader: R,
+    window: usize,
+    position: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl<R: BufRead> Framer<R> {
+    /// `None` if `window` is 0.
+    fn new(reader: R, window: usize) -> Option<Self> {
+        (window > 0).then_some(Self {
+            reader,
+            window,
+            position: 0,
+            started: false,
+            finished: false,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for Framer<R> {
+    type Item = Result<Frame, FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let mut seen = Vec::new();
+            let reader = Recorded {
+                reader: &mut self.reader,
+                seen: &mut seen,
+            };
+            let start = self.position;
+            match find_first_marker_index(reader, self.window) {
+                Ok(Some(read)) => {
+                    self.position += read;
+                    let end = start + read - self.window;
+                    if self.started {
+                        seen.truncate(end - start);
+                        return Some(Ok(Frame {
+                            start,
+                            end,
+                            payload: seen,
+                        }));
+                    }
+                    self.started = true;
+                    if end > start {
+                        return Some(Err(FrameError::Unframed { start, end }));
+                    }
+                }
+                Ok(None) => {
+                    self.finished = true;
+                    let end = start + seen.len();
+                    if end > start && self.started {
+                        return Some(Err(FrameError::Truncated { start, end }));
+                    } else if end > start {
+                        return Some(Err(FrameError::Unframed { start, end }));
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(FrameError::Io(e.kind())));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn main() -> std::io::Result<()> {
     use std::fs::File;
-    use std::io::Read;
+    use std::io::BufReader;
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path).unwrap();
-    let mut input = String::new();
-    file.read_to_string(&mut input).unwrap();
-    println!("{}", find_first_marker_index1(&input));
-    println!("{}", find_first_marker_index2(&input));
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidInput, "window must be > 0");
+    let markers = |window| -> std::io::Result<_> {
+        Markers::new(BufReader::new(File::open(path)?), window).ok_or_else(invalid)
+    };
+
+    // `all N` prints the position of every marker of length N, and `frames N` splits each
+    // line into the frames between markers of length N.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("all") => {
+            for position in markers(args[1].parse().unwrap())? {
+                println!("{}", position?);
+            }
+            return Ok(());
+        }
+        Some("frames") => {
+            let window = args[1].parse().unwrap();
+            for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+                let line = line?;
+                for frame in Framer::new(line.as_bytes(), window).ok_or_else(invalid)? {
+                    match frame {
+                        Ok(frame) => println!(
+                            "{} {}..{} {}",
+                            i + 1,
+                            frame.start,
+                            frame.end,
+                            String::from_utf8_lossy(&frame.payload)
+                        ),
+                        Err(e) => println!("{} {:?}", i + 1, e),
+                    }
+                }
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    for window in [4, 14] {
+        match find_first_marker_index(BufReader::new(File::open(path)?), window)? {
+            Some(position) => println!("{}", position),
+            None => println!("no marker of length {}", window),
+        }
+    }
+    Ok(())
 }
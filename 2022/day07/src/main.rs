@@ -1,5 +1,5 @@
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[cfg(test)]
 mod test {
@@ -31,7 +31,7 @@ $ ls
 
     #[test]
     fn find_first_marker_test1() {
-        let input = parse(TEST_INPUT).unwrap().1;
+        let input = parse(TEST_INPUT).unwrap();
         let mut ip = Vec::<Input>::new();
         for i in input {
             ip.push(i);
@@ -45,247 +45,1021 @@ $ ls
         assert_eq!(Input::InputCommand(Command::ChangeDirectory("a")), ip[6]);
         assert_eq!(Input::InputCommand(Command::List), ip[7]);
     }
+
+    #[test]
+    fn build_file_system_test() {
+        let mut file_system = FileSystem::new();
+        file_system.run(TEST_INPUT).unwrap();
+        let root = file_system.into_root();
+        assert_eq!(48381165, root.size);
+        assert_eq!(94853, root.directories["a"].size);
+        assert_eq!(584, root.directories["a"].directories["e"].size);
+    }
+
+    #[test]
+    fn directory_queries_test() {
+        let mut file_system = FileSystem::new();
+        file_system.run(TEST_INPUT).unwrap();
+        let root = file_system.into_root();
+        let size = |path: &str, size| DirectorySize {
+            path: path.to_string(),
+            size,
+        };
+        assert_eq!(
+            vec![
+                size("/a/e", 584),
+                size("/a", 94853),
+                size("/d", 24933642),
+                size("/", 48381165)
+            ],
+            root.du()
+        );
+        assert_eq!(
+            vec![size("/", 48381165), size("/d", 24933642)],
+            root.largest(2)
+        );
+
+        let small = DirectoryFilter {
+            max_size: Some(100_000),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![size("/a", 94853), size("/a/e", 584)],
+            root.find(&small)
+        );
+        let named = DirectoryFilter {
+            min_size: Some(1000),
+            name: Some("?".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![size("/", 48381165), size("/a", 94853), size("/d", 24933642)],
+            root.find(&named)
+        );
+
+        assert_eq!(
+            Some(size("/d", 24933642)),
+            root.smallest_to_free(30_000_000, 70_000_000)
+        );
+        assert_eq!(Some(size("/a/e", 584)), root.smallest_to_free(1, 48381165));
+        assert_eq!(None, root.smallest_to_free(80_000_000, 70_000_000));
+        assert_eq!(None, root.smallest_to_free(21_618_835, 70_000_000));
+        assert_eq!(None, root.smallest_to_free(0, 48381165));
+        assert_eq!(
+            Some(size("/a/e", 584)),
+            root.smallest_to_free(21_618_836, 70_000_000)
+        );
+    }
+
+    #[test]
+    fn directory_tree_test() {
+        let mut file_system = FileSystem::new();
+        file_system.run(TEST_INPUT).unwrap();
+        assert_eq!(
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)",
+            file_system.into_root().tree()
+        );
+    }
+
+    #[test]
+    fn json_transcript_round_trip_test() {
+        let mut file_system = FileSystem::new();
+        file_system.run(TEST_INPUT).unwrap();
+        let root = file_system.into_root();
+
+        let json = serde_json::to_string(&root).unwrap();
+        let from_json = Directory::from_json(&json).unwrap();
+        assert_eq!(root, from_json);
+
+        let transcript = from_json.to_transcript();
+        let mut file_system = FileSystem::new();
+        file_system.run(&transcript).unwrap();
+        let replayed = file_system.into_root();
+        assert_eq!(root, replayed);
+        assert_eq!(transcript, replayed.to_transcript());
+        assert_eq!(TEST_INPUT.lines().count(), transcript.lines().count());
+        assert!(!transcript.ends_with("$ cd .."));
+    }
+
+    #[test]
+    fn transcript_generation_test() {
+        let mut root = Directory::from_json(
+            r#"{"name": "/", "size": 0, "files": [{"name": "z 1", "size": 3}],
+            "directories": {
+                "empty": {"name": "empty", "size": 99, "files": [], "directories": {}},
+                "x": {"name": "x", "size": 0, "files": [], "directories": {
+                    "y": {"name": "y", "size": 0, "directories": {},
+                        "files": [{"name": "b", "size": 2}, {"name": "a", "size": 1}]}
+                }}
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(6, root.size);
+        assert_eq!(0, root.directories["empty"].size);
+        assert_eq!(
+            "$ cd /
+$ ls
+dir empty
+dir x
+3 z 1
+$ cd x
+$ ls
+dir y
+$ cd y
+$ ls
+2 b
+1 a",
+            root.to_transcript()
+        );
+        root.directories.clear();
+        assert_eq!("$ cd /\n$ ls\n3 z 1", root.to_transcript());
+        assert!(matches!(
+            Directory::from_json("{\"name\": \"/\"}"),
+            Err(TreeError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_json_tree_test() {
+        let mut file_system = FileSystem::new();
+        file_system.run(TEST_INPUT).unwrap();
+        let root = file_system.into_root();
+        let json = serde_json::to_value(&root).unwrap();
+        let from_json = |edit: &dyn Fn(&mut serde_json::Value)| {
+            let mut json = json.clone();
+            edit(&mut json);
+            Directory::from_json(&json.to_string())
+        };
+        assert_eq!(Ok(root), from_json(&|_| {}));
+
+        assert_eq!(
+            Err(TreeError::KeyMismatch {
+                path: "/".to_string(),
+                key: "a".to_string(),
+                name: "z".to_string()
+            }),
+            from_json(&|j| j["directories"]["a"]["name"] = "z".into())
+        );
+        let invalid = |path: &str, name: &str| {
+            Err(TreeError::InvalidName {
+                path: path.to_string(),
+                name: name.to_string(),
+            })
+        };
+        for name in ["", ".", "..", "x/y", "x\ny"] {
+            assert_eq!(
+                invalid("/a", name),
+                from_json(&|j| j["directories"]["a"]["files"][0]["name"] = name.into())
+            );
+            assert_eq!(
+                invalid("/d", name),
+                from_json(&|j| {
+                    let directory = serde_json::json!({
+                        "name": name, "size": 0, "files": [], "directories": {}
+                    });
+                    j["directories"]["d"]["directories"][name] = directory;
+                })
+            );
+        }
+        assert_eq!(
+            invalid("", "root"),
+            from_json(&|j| j["name"] = "root".into())
+        );
+
+        let conflict = |name: &str| {
+            Err(TreeError::Conflict {
+                path: "/a".to_string(),
+                name: name.to_string(),
+            })
+        };
+        assert_eq!(
+            conflict("e"),
+            from_json(&|j| j["directories"]["a"]["files"][0]["name"] = "e".into())
+        );
+        assert_eq!(
+            conflict("g"),
+            from_json(&|j| j["directories"]["a"]["files"][0]["name"] = "g".into())
+        );
+    }
+
+    #[test]
+    fn glob_match_test() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*c", "abbbc"));
+        assert!(glob_match("*.log", "d.log"));
+        assert!(glob_match("?.*", "d.ext"));
+        assert!(!glob_match("*.log", "d.ext"));
+        assert!(!glob_match("a?", "a"));
+        assert!(glob_match("*a*b", "xaab"));
+    }
+
+    #[test]
+    fn file_system_paths_test() {
+        const SESSION: &str = "$ cd /
+$ ls
+dir a
+10 top.txt
+$ cd /a/b-2.x
+$ ls
+20 file name 1
+$ ls
+25 file name 1
+5 .hidden
+$ cd /
+$ ls
+10 top.txt
+$ cd a/./b-2.x/../c
+$ ls
+7 c
+$ cd ../../..
+$ cd ..";
+        let mut file_system = FileSystem::new();
+        file_system.run(SESSION).unwrap();
+        assert!(file_system.cwd.is_empty());
+        let root = file_system.into_root();
+        assert_eq!(10 + 25 + 5 + 7, root.size);
+        let b = &root.directories["a"].directories["b-2.x"];
+        assert_eq!(
+            vec!["file name 1", ".hidden"],
+            b.files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(7, root.directories["a"].directories["c"].size);
+    }
+
+    #[test]
+    fn list_twice_test() {
+        let mut file_system = FileSystem::new();
+        file_system
+            .run("$ cd /\n$ ls\n10 a\n20 b\ndir d\n$ ls\n15 a\n30 c")
+            .unwrap();
+        let root = file_system.into_root();
+        assert_eq!(
+            vec![("a", 15), ("b", 20), ("c", 30)],
+            root.files
+                .iter()
+                .map(|f| (f.name.as_str(), f.size))
+                .collect::<Vec<_>>()
+        );
+        assert!(root.directories.contains_key("d"));
+        assert_eq!(65, root.size);
+    }
+
+    #[test]
+    fn overflow_test() {
+        let half = u64::MAX / 2 + 1;
+        let mut file_system = FileSystem::new();
+        assert_eq!(
+            Err(ShellError::Overflow { line: 7 }),
+            file_system.run(&format!(
+                "$ cd /\n$ ls\ndir x\n{} a\n$ cd x\n$ ls\n{} b",
+                half, half
+            ))
+        );
+        // Listing the same file again replaces its size.
+        let mut file_system = FileSystem::new();
+        file_system
+            .run(&format!("$ cd /\n$ ls\n{} a\n$ ls\n{} a", half, half))
+            .unwrap();
+        assert_eq!(half, file_system.into_root().size);
+
+        let file = |name: &str| serde_json::json!({"name": name, "size": half});
+        let json = serde_json::json!({
+            "name": "/", "size": 0, "files": [file("a")],
+            "directories": {
+                "x": {"name": "x", "size": 0, "files": [file("b")], "directories": {}}
+            }
+        });
+        assert_eq!(
+            Err(TreeError::Overflow),
+            Directory::from_json(&json.to_string())
+        );
+    }
+
+    #[test]
+    fn file_system_errors_test() {
+        let run = |transcript: &str| FileSystem::new().run(transcript);
+        assert_eq!(
+            Err(ShellError::UnknownCommand {
+                line: 2,
+                text: "$ rm -rf a".to_string()
+            }),
+            run("$ cd /\n$ rm -rf a")
+        );
+        assert_eq!(
+            Err(ShellError::Malformed {
+                line: 2,
+                text: "$ cd".to_string()
+            }),
+            run("$ cd /\n$ cd")
+        );
+        assert_eq!(
+            Err(ShellError::Malformed {
+                line: 1,
+                text: "12x a".to_string()
+            }),
+            run("12x a")
+        );
+        assert_eq!(
+            Err(ShellError::UnexpectedOutput { line: 2 }),
+            run("$ cd /\n1 a")
+        );
+        assert_eq!(
+            Err(ShellError::NotADirectory {
+                line: 4,
+                path: "/a".to_string()
+            }),
+            run("$ cd /\n$ ls\n1 a\n$ cd /a")
+        );
+        assert_eq!(
+            Err(ShellError::Conflict {
+                line: 4,
+                name: "a".to_string()
+            }),
+            run("$ cd /\n$ ls\ndir a\n1 a")
+        );
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ShellError {
+    /// `line` is numbered from 1.
+    UnknownCommand {
+        line: usize,
+        text: String,
+    },
+    Malformed {
+        line: usize,
+        text: String,
+    },
+    /// Output from something other than `ls`.
+    UnexpectedOutput {
+        line: usize,
+    },
+    NotADirectory {
+        line: usize,
+        path: String,
+    },
+    /// A listing has a file and a directory with the same name.
+    Conflict {
+        line: usize,
+        name: String,
+    },
+    /// The file takes the files listed so far past what a u64 can hold.
+    Overflow {
+        line: usize,
+    },
+}
+
+impl std::fmt::Display for ShellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShellError::UnknownCommand { line, text } => {
+                write!(f, "line {}: unknown command {:?}", line, text)
+            }
+            ShellError::Malformed { line, text } => {
+                write!(f, "line {}: can't read {:?}", line, text)
+            }
+            ShellError::UnexpectedOutput { line } => write!(f, "line {}: output without ls", line),
+            ShellError::NotADirectory { line, path } => {
+                write!(f, "line {}: {} is not a directory", line, path)
+            }
+            ShellError::Conflict { line, name } => {
+                write!(f, "line {}: {} is both a file and a directory", line, name)
+            }
+            ShellError::Overflow { line } => write!(f, "line {}: the files are too big", line),
+        }
+    }
+}
+
+impl std::error::Error for ShellError {}
+
+/// A tree read from JSON that no transcript could build, `path` is the directory with the
+/// problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TreeError {
+    Json(String),
+    /// A directory is kept under a name that isn't its own.
+    KeyMismatch {
+        path: String,
+        key: String,
+        name: String,
+    },
+    /// Empty, `.`, `..`, or has a `/` or line break in it. The root has to be `/`.
+    InvalidName {
+        path: String,
+        name: String,
+    },
+    /// Two files, or a file and a directory, with the same name.
+    Conflict {
+        path: String,
+        name: String,
+    },
+    /// The files add up to more than a u64 can hold.
+    Overflow,
+}
+
+impl std::fmt::Display for TreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeError::Json(e) => write!(f, "can't read tree: {}", e),
+            TreeError::KeyMismatch { path, key, name } => {
+                write!(f, "{}: {} is kept as {:?}", path, name, key)
+            }
+            TreeError::InvalidName { path, name } => {
+                write!(f, "{}: invalid name {:?}", path, name)
+            }
+            TreeError::Conflict { path, name } => {
+                write!(f, "{}: {} is listed twice", path, name)
+            }
+            TreeError::Overflow => write!(f, "the files are too big to add up"),
+        }
+    }
+}
+
+impl std::error::Error for TreeError {}
+
+#[derive(Debug, Clone, PartialEq)]
 enum Command<'a> {
     List,
     ChangeDirectory(&'a str),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 enum Input<'a> {
     InputCommand(Command<'a>),
     InputDirectory(&'a str),
     InputFile(&'a str, u64),
 }
 
-#[derive(Debug, Serialize)]
-struct Directory<'a> {
-    name: &'a str,
-    directories: HashMap<&'a str, Directory<'a>>,
-    files: Vec<File<'a>>,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Directory {
+    name: String,
+    directories: BTreeMap<String, Directory>,
+    /// In the order they were first listed.
+    files: Vec<File>,
+    /// Everything below this directory, see `update_size`.
     size: u64,
 }
-#[derive(Debug, Serialize)]
-struct File<'a> {
-    name: &'a str,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct File {
+    name: String,
     size: u64,
 }
 
-impl Directory<'_> {
-    fn new<'a>(directory_name: &'a str) -> Directory<'a> {
+impl Directory {
+    fn new(directory_name: &str) -> Directory {
         Directory {
-            name: directory_name,
-            directories: HashMap::<&'a str, Directory<'a>>::new(),
-            files: Vec::<File<'a>>::new(),
+            name: directory_name.to_string(),
+            directories: BTreeMap::new(),
+            files: Vec::new(),
             size: 0u64,
         }
     }
+
+    /// Works out the size of this directory and every one below it, `None` if it is too big to
+    /// hold.
+    fn update_size(&mut self) -> Option<u64> {
+        let mut size = 0u64;
+        for directory in self.directories.values_mut() {
+            size = size.checked_add(directory.update_size()?)?;
+        }
+        for file in self.files.iter() {
+            size = size.checked_add(file.size)?;
+        }
+        self.size = size;
+        Some(size)
+    }
+
+    /// Reads a tree written by `serde_json`, working the sizes out again rather than trusting
+    /// them. The tree is checked so that it could have come from a transcript.
+    fn from_json(json: &str) -> Result<Directory, TreeError> {
+        let mut directory =
+            serde_json::from_str::<Directory>(json).map_err(|e| TreeError::Json(e.to_string()))?;
+        if directory.name != "/" {
+            return Err(TreeError::InvalidName {
+                path: String::new(),
+                name: directory.name,
+            });
+        }
+        directory.check("/")?;
+        directory.update_size().ok_or(TreeError::Overflow)?;
+        Ok(directory)
+    }
+
+    /// Every name below this directory, which is at `path`, could be listed by `ls`.
+    fn check(&self, path: &str) -> Result<(), TreeError> {
+        let invalid = |name: &str| {
+            name.is_empty() || name == "." || name == ".." || name.contains(['/', '\n', '\r'])
+        };
+        let error = |name: &str| TreeError::InvalidName {
+            path: path.to_string(),
+            name: name.to_string(),
+        };
+        let mut files = std::collections::BTreeSet::new();
+        for file in self.files.iter() {
+            if invalid(&file.name) {
+                return Err(error(&file.name));
+            }
+            if self.directories.contains_key(&file.name) || !files.insert(&file.name) {
+                return Err(TreeError::Conflict {
+                    path: path.to_string(),
+                    name: file.name.clone(),
+                });
+            }
+        }
+        for (key, child) in self.directories.iter() {
+            if *key != child.name {
+                return Err(TreeError::KeyMismatch {
+                    path: path.to_string(),
+                    key: key.clone(),
+                    name: child.name.clone(),
+                });
+            }
+            if invalid(key) {
+                return Err(error(key));
+            }
+            child.check(&child_path(path, key))?;
+        }
+        Ok(())
+    }
+
+    /// A transcript that builds this tree when replayed: it lists every directory depth first,
+    /// going in with `cd name` and back out with `cd ..`, skipping empty directories since listing
+    /// their parent makes them. It isn't always the shortest, e.g. an absolute `cd /a/b` can save
+    /// the way out. Names can't have line breaks or `/` in them, or be `.` or `..`, which
+    /// `from_json` checks.
+    fn to_transcript(&self) -> String {
+        fn visit(directory: &Directory, lines: &mut Vec<String>) {
+            lines.push("$ ls".to_string());
+            for name in directory.directories.keys() {
+                lines.push(format!("dir {}", name));
+            }
+            for file in directory.files.iter() {
+                lines.push(format!("{} {}", file.size, file.name));
+            }
+            // Listing the parent is enough to make an empty directory.
+            for (name, child) in directory.directories.iter() {
+                if !child.directories.is_empty() || !child.files.is_empty() {
+                    lines.push(format!("$ cd {}", name));
+                    visit(child, lines);
+                    lines.push("$ cd ..".to_string());
+                }
+            }
+        }
+        let mut lines = vec!["$ cd /".to_string()];
+        visit(self, &mut lines);
+        while lines.last().map(String::as_str) == Some("$ cd ..") {
+            lines.pop();
+        }
+        lines.join("\n")
+    }
+}
+
+/// Replays a shell transcript, keeping track of the current directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileSystem {
+    root: Directory,
+    /// The names of the directories from the root down to the current one.
+    cwd: Vec<String>,
+    /// Whether output lines are expected, i.e. the last command was `ls`.
+    listing: bool,
+    /// The size of every file listed, which no directory can be bigger than.
+    total: u64,
+}
+
+impl FileSystem {
+    fn new() -> Self {
+        FileSystem {
+            root: Directory::new("/"),
+            cwd: Vec::new(),
+            listing: false,
+            total: 0,
+        }
+    }
+
+    fn directory_mut<'a>(root: &'a mut Directory, path: &[String]) -> &'a mut Directory {
+        path.iter().fold(root, |directory, name| {
+            directory.directories.get_mut(name).unwrap()
+        })
+    }
+
+    /// Directories along the way are made if they haven't been listed yet.
+    fn change_directory(&mut self, line: usize, path: &str) -> Result<(), ShellError> {
+        let mut cwd = if path.starts_with('/') {
+            Vec::new()
+        } else {
+            self.cwd.clone()
+        };
+        for part in path.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    cwd.pop();
+                }
+                name => {
+                    let current = FileSystem::directory_mut(&mut self.root, &cwd);
+                    if current.files.iter().any(|f| f.name == name) {
+                        return Err(ShellError::NotADirectory {
+                            line,
+                            path: path.to_string(),
+                        });
+                    }
+                    current
+                        .directories
+                        .entry(name.to_string())
+                        .or_insert_with(|| Directory::new(name));
+                    cwd.push(name.to_string());
+                }
+            }
+        }
+        self.cwd = cwd;
+        Ok(())
+    }
+
+    fn execute(&mut self, line: usize, input: &Input) -> Result<(), ShellError> {
+        let conflict = |name: &str| ShellError::Conflict {
+            line,
+            name: name.to_string(),
+        };
+        match input {
+            Input::InputCommand(Command::ChangeDirectory(path)) => {
+                self.listing = false;
+                self.change_directory(line, path)?;
+            }
+            Input::InputCommand(Command::List) => self.listing = true,
+            _ if !self.listing => return Err(ShellError::UnexpectedOutput { line }),
+            Input::InputDirectory(name) => {
+                let current = FileSystem::directory_mut(&mut self.root, &self.cwd);
+                if current.files.iter().any(|f| f.name == *name) {
+                    return Err(conflict(name));
+                }
+                current
+                    .directories
+                    .entry(name.to_string())
+                    .or_insert_with(|| Directory::new(name));
+            }
+            Input::InputFile(name, size) => {
+                let current = FileSystem::directory_mut(&mut self.root, &self.cwd);
+                if current.directories.contains_key(*name) {
+                    return Err(conflict(name));
+                }
+                // Listing a directory again merges with what was there: a file listed before
+                // takes its new size rather than being counted twice, new files are added and
+                // files that aren't listed again are kept.
+                let file = current.files.iter_mut().find(|f| f.name == *name);
+                let replaced = file.as_ref().map_or(0, |f| f.size);
+                self.total = (self.total - replaced)
+                    .checked_add(*size)
+                    .ok_or(ShellError::Overflow { line })?;
+                match file {
+                    Some(file) => file.size = *size,
+                    None => current.files.push(File {
+                        name: name.to_string(),
+                        size: *size,
+                    }),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, transcript: &str) -> Result<(), ShellError> {
+        for (i, input) in parse(transcript)?.iter().enumerate() {
+            self.execute(i + 1, input)?;
+        }
+        Ok(())
+    }
+
+    /// The whole tree, with the sizes worked out.
+    fn into_root(mut self) -> Directory {
+        self.root
+            .update_size()
+            .expect("every file is counted in the total");
+        self.root
+    }
+}
+
+/// The rest of the line, which has to have something in it.
+fn parse_name(input: &str) -> nom::IResult<&str, &str> {
+    use nom::combinator::{rest, verify};
+    verify(rest, |name: &str| !name.is_empty())(input)
 }
 
 fn parse_command<'a>(input: &'a str) -> nom::IResult<&'a str, Input<'a>> {
     use nom::branch::alt;
-    use nom::bytes::complete::is_a;
     use nom::bytes::complete::tag;
-    use nom::character::complete::char;
-    use nom::sequence::preceded;
-    let (rest, result) = preceded(tag("$ "), alt((tag("ls"), tag("cd"))))(input)?;
-
-    let retval = match result {
-        "ls" => Ok((rest, Input::InputCommand::<'a>(Command::List))),
-        "cd" => {
-            let (rest, directory) = preceded(
-                char(' '),
-                is_a("/abcdefghijklmnopqrstuvwxyz.ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
-            )(rest)?;
-            Ok((
-                rest,
-                Input::InputCommand(Command::ChangeDirectory(directory)),
-            ))
-        }
-        _ => panic!(),
-    };
-    retval
+    use nom::combinator::{eof, map, value};
+    use nom::sequence::{preceded, terminated};
+    preceded(
+        tag("$ "),
+        alt((
+            value(
+                Input::InputCommand(Command::List),
+                terminated(tag("ls"), eof),
+            ),
+            map(preceded(tag("cd "), parse_name), |directory| {
+                Input::InputCommand(Command::ChangeDirectory(directory))
+            }),
+        )),
+    )(input)
 }
 fn parse_directory<'a>(input: &'a str) -> nom::IResult<&'a str, Input<'a>> {
     use nom::bytes::complete::tag;
-    use nom::character::complete::alpha0;
     use nom::sequence::preceded;
-    let (rest, result) = preceded(tag("dir "), alpha0)(input)?;
+    let (rest, result) = preceded(tag("dir "), parse_name)(input)?;
 
     Ok((rest, Input::InputDirectory(result)))
 }
 fn parse_file<'a>(input: &'a str) -> nom::IResult<&'a str, Input<'a>> {
-    use nom::bytes::complete::is_a;
-    use nom::character::complete::char;
-    use nom::character::complete::digit0;
+    use nom::character::complete::{char, u64};
     use nom::sequence::separated_pair;
-    let (rest, result) = separated_pair(
-        digit0,
-        char(' '),
-        is_a("abcdefghijklmnopqrstuvwxyz.ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
-    )(input)?;
+    let (rest, result) = separated_pair(u64, char(' '), parse_name)(input)?;
 
-    Ok((rest, Input::InputFile(result.1, result.0.parse().unwrap())))
+    Ok((rest, Input::InputFile(result.1, result.0)))
 }
 fn parse_input<'a>(input: &'a str) -> nom::IResult<&'a str, Input<'a>> {
     use nom::branch::alt;
-    let (rest, result) = alt((parse_command, parse_directory, parse_file))(input)?;
+    use nom::combinator::all_consuming;
+    let (rest, result) = all_consuming(alt((parse_command, parse_directory, parse_file)))(input)?;
     Ok((rest, result))
 }
 
-fn parse<'a>(input: &'a str) -> nom::IResult<&'a str, std::collections::VecDeque<Input<'a>>> {
-    use nom::character::complete::newline;
-    use nom::multi::separated_list0;
+/// One input for every line of the transcript.
+fn parse(input: &str) -> Result<Vec<Input<'_>>, ShellError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, l)| match parse_input(l) {
+            Ok((_, result)) => Ok(result),
+            Err(_) => {
+                let text = l.to_string();
+                let command = l.strip_prefix("$ ").map(|c| c.split(' ').next());
+                match command {
+                    Some(Some("cd" | "ls")) | None => {
+                        Err(ShellError::Malformed { line: i + 1, text })
+                    }
+                    Some(_) => Err(ShellError::UnknownCommand { line: i + 1, text }),
+                }
+            }
+        })
+        .collect()
+}
 
-    let mut retval = std::collections::VecDeque::<Input<'a>>::new();
+/// A directory picked out by a query, `path` is from the root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DirectorySize {
+    path: String,
+    size: u64,
+}
 
-    let (rest, values) = separated_list0(newline, parse_input)(input)?;
+/// Which directories `Directory::find` returns, everything when left as the default.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct DirectoryFilter {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// A glob over the directory's name, `*` matches any run of characters and `?` any one.
+    name: Option<String>,
+}
 
-    for i in values {
-        retval.push_back(i);
+impl DirectoryFilter {
+    fn matches(&self, directory: &Directory) -> bool {
+        self.min_size.is_none_or(|min| directory.size >= min)
+            && self.max_size.is_none_or(|max| directory.size <= max)
+            && self
+                .name
+                .as_deref()
+                .is_none_or(|pattern| glob_match(pattern, &directory.name))
     }
-
-    Ok((rest, retval))
 }
 
-fn build_from_input<'a>(
-    inputs: &mut std::collections::VecDeque<Input<'a>>,
-    current_directory: &mut Directory<'a>,
-) {
-    while let Some(input) = inputs.pop_front() {
-        match input {
-            Input::InputDirectory(directory_name) => {
-                if !current_directory.directories.contains_key(directory_name) {
-                    let directory = Directory::new(directory_name);
-                    current_directory
-                        .directories
-                        .insert(directory_name, directory);
-                }
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Where to go back to when what follows the last `*` stops matching.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
             }
-            Input::InputFile(name, size) => {
-                current_directory.files.push(File { name, size });
-                current_directory.size += size;
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
             }
-            Input::InputCommand(command) => match command {
-                Command::ChangeDirectory(directory_name) => {
-                    if directory_name != ".." {
-                        let directory_option = current_directory.directories.remove(directory_name);
-                        let mut directory = if directory_option.is_some() {
-                            directory_option.unwrap()
-                        } else {
-                            Directory::new(directory_name)
-                        };
-                        build_from_input(inputs, &mut directory);
-                        current_directory.size += directory.size;
-                        current_directory
-                            .directories
-                            .insert(directory_name, directory);
-                    } else {
-                        return;
-                    }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
                 }
-                Command::List => {}
+                None => return false,
             },
-            _ => panic!(),
         }
     }
-}
-fn build_from_input_initialise<'a>(
-    input: &mut std::collections::VecDeque<Input<'a>>,
-) -> anyhow::Result<Directory<'a>> {
-    let Some(cmd) = input.pop_front() else { panic!("No inputs to initialise with") };
-    let Input::InputCommand(Command::ChangeDirectory(init_cmd)) = cmd else { panic!("Shouldn't get here bad input.")        
-    };
-    let mut current_directory = Directory::new(init_cmd);
-    build_from_input(input, &mut current_directory);
-    let dsize = current_directory
-        .directories
-        .values()
-        .map(|i| i.size)
-        .sum::<u64>();
-    let fsize = current_directory.files.iter().map(|i| i.size).sum::<u64>();
-    current_directory.size = dsize + fsize;
-    Ok(current_directory)
+    pattern[p..].iter().all(|c| *c == '*')
 }
 
-fn sum_directories_of_limit(directory: &Directory, limit: u64) -> u64 {
-    let mut answer = directory
-        .directories
-        .values()
-        .map(|d| sum_directories_of_limit(d, limit))
-        .sum::<u64>();
-    answer += if directory.size <= limit {
-        directory.size
+fn child_path(parent: &str, name: &str) -> String {
+    if parent.ends_with('/') {
+        format!("{}{}", parent, name)
     } else {
-        0
-    };
-    answer
+        format!("{}/{}", parent, name)
+    }
 }
 
-fn find_min_to_free(directory: &Directory, need_to_free: u64) -> u64 {
-    let mut to_free = u64::max_value();
-    to_free = directory
-        .directories
-        .values()
-        .map(|d| find_min_to_free(d, need_to_free))
-        .min()
-        .unwrap_or_else(|| to_free);
-    to_free = if directory.size > need_to_free && directory.size < to_free {
-        directory.size
-    } else {
-        to_free
-    };
-    to_free
+impl Directory {
+    /// Every directory like `du`, each one after the directories inside it.
+    fn du(&self) -> Vec<DirectorySize> {
+        fn visit(directory: &Directory, path: String, sizes: &mut Vec<DirectorySize>) {
+            for child in directory.directories.values() {
+                visit(child, child_path(&path, &child.name), sizes);
+            }
+            sizes.push(DirectorySize {
+                path,
+                size: directory.size,
+            });
+        }
+        let mut sizes = Vec::new();
+        visit(self, self.name.clone(), &mut sizes);
+        sizes
+    }
+
+    /// Every directory and file, a line each, with the entries of a directory sorted by name.
+    fn tree(&self) -> String {
+        fn visit(directory: &Directory, depth: usize, lines: &mut Vec<String>) {
+            lines.push(format!(
+                "{}- {} (dir, size={})",
+                "  ".repeat(depth),
+                directory.name,
+                directory.size
+            ));
+            let mut entries = directory
+                .directories
+                .values()
+                .map(|d| (d.name.as_str(), Some(d), d.size))
+                .chain(
+                    directory
+                        .files
+                        .iter()
+                        .map(|f| (f.name.as_str(), None, f.size)),
+                )
+                .collect::<Vec<_>>();
+            entries.sort_by_key(|(name, _, _)| *name);
+            for (name, child, size) in entries {
+                match child {
+                    Some(child) => visit(child, depth + 1, lines),
+                    None => lines.push(format!(
+                        "{}- {} (file, size={})",
+                        "  ".repeat(depth + 1),
+                        name,
+                        size
+                    )),
+                }
+            }
+        }
+        let mut lines = Vec::new();
+        visit(self, 0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn find(&self, filter: &DirectoryFilter) -> Vec<DirectorySize> {
+        let mut found = Vec::new();
+        let mut stack = vec![(self.name.clone(), self)];
+        while let Some((path, directory)) = stack.pop() {
+            for child in directory.directories.values().rev() {
+                stack.push((child_path(&path, &child.name), child));
+            }
+            if filter.matches(directory) {
+                found.push(DirectorySize {
+                    path,
+                    size: directory.size,
+                });
+            }
+        }
+        found
+    }
+
+    /// The `n` largest directories, largest first.
+    fn largest(&self, n: usize) -> Vec<DirectorySize> {
+        let mut sizes = self.du();
+        sizes.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+        sizes.truncate(n);
+        sizes
+    }
+
+    /// The smallest directory to delete to have `needed` free on a disk of `capacity`, none if
+    /// there is already enough free or nothing is big enough.
+    fn smallest_to_free(&self, needed: u64, capacity: u64) -> Option<DirectorySize> {
+        let free = capacity.saturating_sub(self.size);
+        if free >= needed {
+            return None;
+        }
+        let filter = DirectoryFilter {
+            min_size: Some(needed.saturating_sub(free)),
+            ..Default::default()
+        };
+        self.find(&filter).into_iter().min_by_key(|d| d.size)
+    }
 }
 
-fn main() {
-    use std::fs::File;
+fn main() -> anyhow::Result<()> {
     use std::io::Read;
     use std::path::Path;
 
-    const TEST_INPUT: &str = "$ cd /
-$ ls
-dir a
-14848514 b.txt
-8504156 c.dat
-dir d
-$ cd a
-$ ls
-dir e
-29116 f
-2557 g
-62596 h.lst
-$ cd e
-$ ls
-584 i
-$ cd ..
-$ cd ..
-$ cd d
-$ ls
-4060174 j
-8033020 d.log
-5626152 d.ext
-7214296 k";
+    // `from=FILE` reads a tree saved by `tree json` instead of replaying input.txt.
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let from = args
+        .first()
+        .and_then(|a| a.strip_prefix("from="))
+        .map(str::to_string);
+    let directory = match from {
+        Some(from) => {
+            args.remove(0);
+            Directory::from_json(&std::fs::read_to_string(from)?)?
+        }
+        None => {
+            let path = Path::new("input.txt");
+            let mut file = std::fs::File::open(path)?;
+            let mut input = String::new();
+            file.read_to_string(&mut input)?;
+            let mut file_system = FileSystem::new();
+            file_system.run(&input)?;
+            file_system.into_root()
+        }
+    };
 
-    let path = Path::new("input.txt");
-    let mut file = File::open(&path).unwrap();
-    let mut input = String::new();
-    file.read_to_string(&mut input).unwrap();
-    let mut inputs = parse(&input).unwrap().1;
-    // let mut inputs = parse(TEST_INPUT).unwrap().1;
-    let directory = build_from_input_initialise(&mut inputs).unwrap();
-    println!("{:?}", directory);
-    println!("{}", serde_json::to_string_pretty(&directory).unwrap());
-    let answer = sum_directories_of_limit(&directory, 100_000);
-    println!("{}", answer);
-    let remaining = 70_000_000 - directory.size;
-    let need_to_free = 30_000_000 - remaining;
-
-    let free_amount = find_min_to_free(&directory, need_to_free);
-    println!("{}", free_amount);
-
-    drop(inputs);
-    drop(input);
+    // `du`, `tree`, `top N`, `find [min=N] [max=N] [name=GLOB]` and `free NEEDED CAPACITY` query
+    // the tree, printing JSON instead of text when the last argument is `json`. `transcript`
+    // prints the commands that would build it.
+    let json = args.last().map(String::as_str) == Some("json");
+    if json {
+        args.pop();
+    }
+    let print = |sizes: Vec<DirectorySize>| -> anyhow::Result<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(&sizes)?);
+        } else {
+            for d in sizes {
+                println!("{}\t{}", d.size, d.path);
+            }
+        }
+        Ok(())
+    };
+    match args.first().map(String::as_str) {
+        Some("du") => print(directory.du())?,
+        Some("tree") if json => println!("{}", serde_json::to_string_pretty(&directory)?),
+        Some("tree") => println!("{}", directory.tree()),
+        Some("transcript") => println!("{}", directory.to_transcript()),
+        Some("top") => print(directory.largest(args[1].parse()?))?,
+        Some("find") => {
+            let mut filter = DirectoryFilter::default();
+            for arg in args[1..].iter() {
+                match arg.split_once('=') {
+                    Some(("min", n)) => filter.min_size = Some(n.parse()?),
+                    Some(("max", n)) => filter.max_size = Some(n.parse()?),
+                    Some(("name", glob)) => filter.name = Some(glob.to_string()),
+                    _ => anyhow::bail!("unknown filter {}", arg),
+                }
+            }
+            print(directory.find(&filter))?
+        }
+        Some("free") => {
+            let smallest = directory.smallest_to_free(args[1].parse()?, args[2].parse()?);
+            print(smallest.into_iter().collect())?
+        }
+        Some(other) => anyhow::bail!("unknown query {}", other),
+        None => {
+            let small = DirectoryFilter {
+                max_size: Some(100_000),
+                ..Default::default()
+            };
+            let answer = directory.find(&small).iter().map(|d| d.size).sum::<u64>();
+            println!("{}", answer);
+            let (needed, capacity) = (30_000_000, 70_000_000);
+            match directory.smallest_to_free(needed, capacity) {
+                Some(d) => println!("{}", d.size),
+                None if capacity.saturating_sub(directory.size) >= needed => {
+                    println!("already enough free")
+                }
+                None => println!("nothing big enough to free"),
+            }
+        }
+    }
+    Ok(())
 }
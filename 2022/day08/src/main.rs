@@ -8,14 +8,228 @@ const TEST_INPUT: &str = "30373
 mod test {
     use super::*;
 
+    // The first implementation, which the stack based one is checked against.
+    #[derive(PartialEq)]
+    enum RunGridDirection {
+        Forward,
+        Reverse,
+    }
+
+    fn run_grid_direction_x(
+        grid: &mut [Vec<Tree>],
+        xlen: usize,
+        ylen: usize,
+        direction: RunGridDirection,
+    ) {
+        let yiterator = if direction == RunGridDirection::Forward {
+            (0..ylen).collect::<Vec<usize>>()
+        } else {
+            (0..ylen).rev().collect::<Vec<usize>>()
+        };
+        for y in yiterator {
+            let mut max_height_found = -1;
+            for x in 0..xlen {
+                max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+            }
+        }
+        let yiterator = if direction == RunGridDirection::Forward {
+            (0..ylen).collect::<Vec<usize>>()
+        } else {
+            (0..ylen).rev().collect::<Vec<usize>>()
+        };
+        for y in yiterator {
+            let mut max_height_found = -1;
+            for x in (0..xlen).rev() {
+                max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+            }
+        }
+    }
+
+    fn run_grid_direction_y(
+        grid: &mut [Vec<Tree>],
+        xlen: usize,
+        ylen: usize,
+        direction: RunGridDirection,
+    ) {
+        let xiterator = if direction == RunGridDirection::Forward {
+            (0..xlen).collect::<Vec<usize>>()
+        } else {
+            (0..xlen).rev().collect::<Vec<usize>>()
+        };
+        for x in xiterator {
+            let mut max_height_found = -1;
+            for y in 0..ylen {
+                max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+            }
+        }
+        let xiterator = if direction == RunGridDirection::Forward {
+            (0..xlen).collect::<Vec<usize>>()
+        } else {
+            (0..xlen).rev().collect::<Vec<usize>>()
+        };
+        for x in xiterator {
+            let mut max_height_found = -1;
+            for y in (0..ylen).rev() {
+                max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+            }
+        }
+    }
+
+    fn run_grid_apply_rules(
+        grid: &mut [Vec<Tree>],
+        x: usize,
+        y: usize,
+        max_height_found: i32,
+    ) -> i32 {
+        let height = grid[y][x].height;
+        let visible = grid[y][x].visible;
+        grid[y][x].visible = height > max_height_found || visible;
+        std::cmp::max(height, max_height_found)
+    }
+
+    fn run_grid(grid: &mut [Vec<Tree>]) {
+        let xlen = grid[0].len();
+        let ylen = grid.len();
+
+        run_grid_direction_x(grid, xlen, ylen, RunGridDirection::Forward);
+        run_grid_direction_x(grid, xlen, ylen, RunGridDirection::Reverse);
+        run_grid_direction_y(grid, xlen, ylen, RunGridDirection::Forward);
+        run_grid_direction_y(grid, xlen, ylen, RunGridDirection::Reverse);
+    }
+
+    fn score_direction<FX, FY>(
+        grid: &[Vec<Tree>],
+        ceiling: i32,
+        x: usize,
+        y: usize,
+        nextx: FX,
+        nexty: FY,
+    ) -> usize
+    where
+        FX: Fn(i32) -> i32,
+        FY: Fn(i32) -> i32,
+    {
+        let mut score = 0;
+        let mut max_height_found = 0i32;
+        let xmax = grid[0].len() as i32;
+        let ymax = grid.len() as i32;
+        let mut x = nextx(x as i32);
+        let mut y = nexty(y as i32);
+        while max_height_found < ceiling && x >= 0i32 && x < xmax && y >= 0i32 && y < ymax {
+            max_height_found = std::cmp::max(max_height_found, grid[y as usize][x as usize].height);
+
+            score += 1;
+            x = nextx(x);
+            y = nexty(y);
+        }
+        score
+    }
+
+    fn score_grid_position(grid: &mut [Vec<Tree>], x: usize, y: usize) -> usize {
+        let ceiling = grid[y][x].height;
+        let l = score_direction(grid, ceiling, x, y, |x| x - 1, |y| y);
+        let r = score_direction(grid, ceiling, x, y, |x| x + 1, |y| y);
+        let u = score_direction(grid, ceiling, x, y, |x| x, |y| y - 1);
+        let d = score_direction(grid, ceiling, x, y, |x| x, |y| y + 1);
+        let score = l * r * u * d;
+        grid[y][x].view_score = score as u128;
+        score
+    }
+
+    fn score_grid(grid: &mut [Vec<Tree>]) {
+        let xmax = grid[0].len();
+        let ymax = grid.len();
+        for x in 0..xmax {
+            for y in 0..ymax {
+                score_grid_position(grid, x, y);
+            }
+        }
+    }
+
+    /// A grid of pseudo-random digits.
+    fn random_input(xlen: usize, ylen: usize, seed: u64) -> String {
+        let mut state = seed;
+        (0..ylen)
+            .map(|_| {
+                (0..xlen)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        char::from_digit(((state >> 33) % 10) as u32, 10).unwrap()
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn analyse_grid_matches_first_implementation_test() {
+        for (i, (xlen, ylen)) in [(5, 5), (1, 1), (1, 17), (23, 1), (40, 31), (97, 64)]
+            .into_iter()
+            .enumerate()
+        {
+            let input = random_input(xlen, ylen, i as u64);
+            for input in [TEST_INPUT, &input] {
+                let mut expected = create_grid(input).unwrap();
+                run_grid(&mut expected);
+                score_grid(&mut expected);
+                let mut grid = create_grid(input).unwrap();
+                analyse_grid(&mut grid, &Sight::default());
+                for (expected, tree) in expected.iter().flatten().zip(grid.iter().flatten()) {
+                    assert_eq!(expected.visible, tree.visible);
+                    // The first implementation never lets a tree of height 0 see anything.
+                    if tree.height > 0 {
+                        assert_eq!(expected.view_score, tree.view_score);
+                    }
+                }
+            }
+        }
+
+        // Looking from a tree of height 0 stops at the next tree.
+        let mut grid = create_grid("303\n202\n303").unwrap();
+        analyse_grid(&mut grid, &Sight::default());
+        assert_eq!(1, grid[1][1].view_score);
+    }
+
+    #[test]
+    fn analyse_large_grid_test() {
+        let input = random_input(1000, 1000, 7);
+        let mut grid = create_grid(&input).unwrap();
+        analyse_grid(&mut grid, &Sight::default());
+        // Every tree on the edge can be seen.
+        assert!(grid[0].iter().chain(grid[999].iter()).all(|t| t.visible));
+        assert!(grid.iter().all(|row| row[0].visible && row[999].visible));
+
+        // A tall tree in the middle sees 300 trees each way, too many to score in a u64.
+        let mut rows = vec!["1".repeat(601); 601];
+        rows[300].replace_range(300..301, "9");
+        let mut grid = create_grid(&rows.join("\n")).unwrap();
+        let sight = Sight {
+            diagonals: true,
+            ..Default::default()
+        };
+        analyse_grid(&mut grid, &sight);
+        assert_eq!(300u128.pow(8), grid[300][300].view_score);
+        assert_eq!(300u128.pow(8), highest_view_score(&grid));
+        // The trees next to it see one tree towards it.
+        assert_eq!(1, grid[300][299].view_score);
+
+        // Only the middle tree is white, besides the 255 in the header.
+        let pgm = to_pgm(&grid, Field::Score);
+        assert_eq!(Some("0 0 0"), pgm.lines().nth(3).map(|l| &l[..5]));
+        assert_eq!(1, pgm.matches("255").count() - 1);
+    }
+
     #[test]
     fn get_total_visible_trees_test() {
-        assert_eq!(21, get_total_visible_trees(TEST_INPUT));
+        assert_eq!(Ok(21), get_total_visible_trees(TEST_INPUT));
     }
 
     #[test]
     fn get_best_view_score() {
-        let mut grid = create_grid(TEST_INPUT);
+        let mut grid = create_grid(TEST_INPUT).unwrap();
         score_grid(&mut grid);
         assert_eq!(
             8,
@@ -26,6 +240,93 @@ mod test {
         );
         // dbg!(grid);
         // assert!(false);
+        assert_eq!(Ok(8), get_highest_view_score(TEST_INPUT));
+    }
+
+    #[test]
+    fn separated_heights_test() {
+        let grid = create_grid("10, 2,300\n4 5 6\n7,8,9").unwrap();
+        assert_eq!(
+            vec![10, 2, 300, 4, 5, 6, 7, 8, 9],
+            grid.iter().flatten().map(|t| t.height).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(GridError::InvalidHeight {
+                line: 2,
+                column: 3,
+                text: "x".to_string()
+            }),
+            create_grid("123\n45x").map(|_| ())
+        );
+        assert_eq!(
+            Err(GridError::Ragged {
+                line: 2,
+                expected: 3,
+                found: 2
+            }),
+            create_grid("1 2 3\n4 5").map(|_| ())
+        );
+    }
+
+    #[test]
+    fn diagonal_sight_test() {
+        const INPUT: &str = "19999
+91999
+99599
+99999
+99999";
+        let mut grid = create_grid(INPUT).unwrap();
+        analyse_grid(&mut grid, &Sight::default());
+        assert!(!grid[2][2].visible);
+        assert_eq!(1, grid[2][2].view_score);
+
+        let sight = Sight {
+            diagonals: true,
+            ..Default::default()
+        };
+        analyse_grid(&mut grid, &sight);
+        // The 5 sees past the 1s to the top left corner.
+        assert!(grid[2][2].visible);
+        assert_eq!(2, grid[2][2].view_score);
+        // The 1 next to it is hidden by the 1 in the corner.
+        assert!(!grid[1][1].visible);
+        // Trees on the edge have a direction they can't see in at all.
+        assert_eq!(0, grid[0][2].view_score);
+    }
+
+    #[test]
+    fn transparent_trees_test() {
+        const INPUT: &str = "9 9 9 9 9
+1 3 2 3 1
+9 9 9 9 9";
+        let mut grid = create_grid(INPUT).unwrap();
+        analyse_grid(&mut grid, &Sight::default());
+        assert!(!grid[1][2].visible);
+        assert_eq!(1, grid[1][2].view_score);
+
+        let sight = Sight {
+            transparent_below: 4,
+            ..Default::default()
+        };
+        analyse_grid(&mut grid, &sight);
+        // The 2 sees through the 3s on either side.
+        assert!(grid[1][2].visible);
+        assert_eq!(2 * 2, grid[1][2].view_score);
+        // So do the 3s, out to both edges.
+        assert!(grid[1][1].visible);
+        assert_eq!(3, grid[1][1].view_score);
+    }
+
+    #[test]
+    fn heatmap_test() {
+        let mut grid = create_grid("303\n2 5 2\n303").unwrap();
+        analyse_grid(&mut grid, &Sight::default());
+        assert_eq!("1,1,1\n1,1,1\n1,1,1", to_csv(&grid, Field::Visible));
+        assert_eq!("0,0,0\n0,1,0\n0,0,0", to_csv(&grid, Field::Score));
+        assert_eq!(
+            "P2\n3 3\n255\n0 0 0\n0 255 0\n0 0 0\n",
+            to_pgm(&grid, Field::Score)
+        );
     }
 }
 
@@ -33,22 +334,45 @@ mod test {
 struct Tree {
     height: i32,
     visible: bool,
-    view_score: usize,
+    /// Saturates rather than overflowing, which only a grid with billions of trees could do.
+    view_score: u128,
 }
 
-#[derive(PartialEq)]
-enum RunGridDirection {
-    Forward,
-    Reverse,
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GridError {
+    /// `line` and `column` are numbered from 1, `column` counts heights rather than characters.
+    InvalidHeight {
+        line: usize,
+        column: usize,
+        text: String,
+    },
+    /// Every row needs as many trees as the first.
+    Ragged {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
 }
 
-fn create_grid(input: &str) -> Vec<Vec<Tree>> {
+/// Rows are either a digit per tree, or heights of any size separated by spaces or commas.
+fn create_grid(input: &str) -> Result<Vec<Vec<Tree>>, GridError> {
     let mut grid = Vec::<Vec<Tree>>::new();
-    for line in input.lines() {
-        let mut xaxis = Vec::<Tree>::with_capacity(line.len());
+    for (i, line) in input.lines().enumerate() {
+        let heights = if line.contains(|c: char| c == ',' || c.is_whitespace()) {
+            line.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|h| !h.is_empty())
+                .collect::<Vec<_>>()
+        } else {
+            line.split("").filter(|h| !h.is_empty()).collect::<Vec<_>>()
+        };
+        let mut xaxis = Vec::<Tree>::with_capacity(heights.len());
 
-        for c in line.chars() {
-            let height = c.to_digit(10).unwrap() as i32;
+        for (column, text) in heights.into_iter().enumerate() {
+            let height = text.parse::<i32>().map_err(|_| GridError::InvalidHeight {
+                line: i + 1,
+                column: column + 1,
+                text: text.to_string(),
+            })?;
             xaxis.push(Tree {
                 height,
                 visible: false,
@@ -56,165 +380,189 @@ fn create_grid(input: &str) -> Vec<Vec<Tree>> {
             });
         }
 
+        if let Some(first) = grid.first() {
+            if first.len() != xaxis.len() {
+                return Err(GridError::Ragged {
+                    line: i + 1,
+                    expected: first.len(),
+                    found: xaxis.len(),
+                });
+            }
+        }
         grid.push(xaxis);
     }
-    grid
+    Ok(grid)
 }
 
-fn run_grid_direction_x(
-    grid: &mut Vec<Vec<Tree>>,
-    xlen: usize,
-    ylen: usize,
-    direction: RunGridDirection,
-) {
-    let yiterator = if direction == RunGridDirection::Forward {
-        (0..ylen).collect::<Vec<usize>>()
-    } else {
-        (0..ylen).rev().collect::<Vec<usize>>()
-    };
-    for y in yiterator {
-        let mut max_height_found = -1;
-        for x in 0..xlen {
-            max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+/// Which way trees look, and which trees they can see through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Sight {
+    /// Look along the diagonals as well as the rows and columns.
+    diagonals: bool,
+    /// Trees lower than this don't block the view.
+    transparent_below: i32,
+}
+
+impl Sight {
+    /// Steps as (dx, dy).
+    fn directions(&self) -> &'static [(isize, isize)] {
+        const DIRECTIONS: [(isize, isize); 8] = [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+        ];
+        if self.diagonals {
+            &DIRECTIONS
+        } else {
+            &DIRECTIONS[..4]
         }
     }
-    let yiterator = if direction == RunGridDirection::Forward {
-        (0..ylen).collect::<Vec<usize>>()
-    } else {
-        (0..ylen).rev().collect::<Vec<usize>>()
-    };
-    for y in yiterator {
-        let mut max_height_found = -1;
-        for x in (0..xlen).rev() {
-            max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+}
+
+/// Marks the trees that can be seen from outside the grid and scores the view from each, with
+/// one pass along every line of trees in each direction.
+fn analyse_grid(grid: &mut [Vec<Tree>], sight: &Sight) {
+    let ylen = grid.len() as isize;
+    let xlen = grid.first().map_or(0, Vec::len) as isize;
+    for tree in grid.iter_mut().flatten() {
+        tree.visible = false;
+        tree.view_score = 1;
+    }
+    let inside = move |(x, y): (isize, isize)| x >= 0 && x < xlen && y >= 0 && y < ylen;
+    let mut stack = Vec::with_capacity(xlen.max(ylen) as usize);
+    for &(dx, dy) in sight.directions() {
+        // A line starts wherever the step before it would be off the grid.
+        for start in (0..ylen).flat_map(|y| (0..xlen).map(move |x| (x, y))) {
+            if inside((start.0 - dx, start.1 - dy)) {
+                continue;
+            }
+            let line = std::iter::successors(Some(start), |(x, y)| Some((x + dx, y + dy)))
+                .take_while(|p| inside(*p))
+                .map(|(x, y)| (x as usize, y as usize));
+            sweep(grid, line, sight.transparent_below, &mut stack);
         }
     }
 }
 
-fn run_grid_direction_y(
-    grid: &mut Vec<Vec<Tree>>,
-    xlen: usize,
-    ylen: usize,
-    direction: RunGridDirection,
+/// Looks back along a line of trees, given as (x, y), from each one in turn. The stack holds
+/// the trees behind that no taller tree has hidden yet, tallest at the bottom, so each tree is
+/// pushed and popped once. Trees lower than `transparent_below` are never put on the stack.
+fn sweep(
+    grid: &mut [Vec<Tree>],
+    line: impl Iterator<Item = (usize, usize)>,
+    transparent_below: i32,
+    stack: &mut Vec<(i32, usize)>,
 ) {
-    let xiterator = if direction == RunGridDirection::Forward {
-        (0..xlen).collect::<Vec<usize>>()
-    } else {
-        (0..xlen).rev().collect::<Vec<usize>>()
-    };
-    for x in xiterator {
-        let mut max_height_found = -1;
-        for y in 0..ylen {
-            max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+    stack.clear();
+    for (i, (x, y)) in line.enumerate() {
+        let tree = &mut grid[y][x];
+        while stack
+            .last()
+            .is_some_and(|(height, _)| *height < tree.height)
+        {
+            stack.pop();
         }
-    }
-    let xiterator = if direction == RunGridDirection::Forward {
-        (0..xlen).collect::<Vec<usize>>()
-    } else {
-        (0..xlen).rev().collect::<Vec<usize>>()
-    };
-    for x in xiterator {
-        let mut max_height_found = -1;
-        for y in (0..ylen).rev() {
-            max_height_found = run_grid_apply_rules(grid, x, y, max_height_found);
+        match stack.last() {
+            Some((_, blocked_at)) => {
+                tree.view_score = tree.view_score.saturating_mul((i - blocked_at) as u128)
+            }
+            None => {
+                tree.visible = true;
+                tree.view_score = tree.view_score.saturating_mul(i as u128);
+            }
+        }
+        if tree.height >= transparent_below {
+            stack.push((tree.height, i));
         }
     }
 }
 
-fn run_grid_apply_rules(
-    grid: &mut Vec<Vec<Tree>>,
-    x: usize,
-    y: usize,
-    max_height_found: i32,
-) -> i32 {
-    let height = grid[y][x].height;
-    let visible = grid[y][x].visible;
-    grid[y][x].visible = height > max_height_found || visible;
-    std::cmp::max(height, max_height_found)
+/// What to draw for each tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// 1 if the tree can be seen from outside, otherwise 0.
+    Visible,
+    Score,
 }
 
-fn run_grid(grid: &mut Vec<Vec<Tree>>) {
-    let xlen = grid[0].len();
-    let ylen = grid.len();
+impl Field {
+    fn value(&self, tree: &Tree) -> u128 {
+        match self {
+            Field::Visible => tree.visible as u128,
+            Field::Score => tree.view_score,
+        }
+    }
+}
 
-    run_grid_direction_x(grid, xlen, ylen, RunGridDirection::Forward);
-    run_grid_direction_x(grid, xlen, ylen, RunGridDirection::Reverse);
-    run_grid_direction_y(grid, xlen, ylen, RunGridDirection::Forward);
-    run_grid_direction_y(grid, xlen, ylen, RunGridDirection::Reverse);
+fn to_csv(grid: &[Vec<Tree>], field: Field) -> String {
+    grid.iter()
+        .map(|row| {
+            row.iter()
+                .map(|t| field.value(t).to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A plain (P2) greyscale image, with the highest value white.
+fn to_pgm(grid: &[Vec<Tree>], field: Field) -> String {
+    let max = grid
+        .iter()
+        .flatten()
+        .map(|t| field.value(t))
+        .max()
+        .unwrap_or(0);
+    let shade = |tree: &Tree| match max {
+        0 => 0,
+        // In floating point, as the value times 255 can be too big for an integer.
+        _ => (field.value(tree) as f64 * 255.0 / max as f64) as u8,
+    };
+    let mut pgm = format!(
+        "P2\n{} {}\n255\n",
+        grid.first().map_or(0, Vec::len),
+        grid.len()
+    );
+    for row in grid {
+        let row = row.iter().map(|t| shade(t).to_string()).collect::<Vec<_>>();
+        pgm.push_str(&row.join(" "));
+        pgm.push('\n');
+    }
+    pgm
 }
 
-fn count_visible_trees(grid: &Vec<Vec<Tree>>) -> usize {
+fn count_visible_trees(grid: &[Vec<Tree>]) -> usize {
     grid.iter()
         .map(|a| a.iter().filter(|t| t.visible).count())
         .sum()
 }
 
-fn get_total_visible_trees(input: &str) -> usize {
-    let mut grid = create_grid(input);
-    // println!("{:?}", grid);
-    run_grid(&mut grid);
-    // println!("{:?}", grid);
+fn get_total_visible_trees(input: &str) -> Result<usize, GridError> {
+    let mut grid = create_grid(input)?;
+    analyse_grid(&mut grid, &Sight::default());
     let visible_trees_count = count_visible_trees(&grid);
     println!("{:?}", visible_trees_count);
-    visible_trees_count
-}
-
-fn score_direction<FX, FY>(
-    grid: &Vec<Vec<Tree>>,
-    ceiling: i32,
-    x: usize,
-    y: usize,
-    nextx: FX,
-    nexty: FY,
-) -> usize
-where
-    FX: Fn(i32) -> i32,
-    FY: Fn(i32) -> i32,
-{
-    let mut score = 0;
-    let mut max_height_found = 0i32;
-    let xmax = grid[0].len() as i32;
-    let ymax = grid.len() as i32;
-    let mut x = nextx(x as i32);
-    let mut y = nexty(y as i32);
-    while max_height_found < ceiling && x >= 0i32 && x < xmax && y >= 0i32 && y < ymax {
-        max_height_found = std::cmp::max(max_height_found, grid[y as usize][x as usize].height);
-
-        score += 1;
-        x = nextx(x);
-        y = nexty(y);
-    }
-    score
-}
-
-fn score_grid_position(grid: &mut Vec<Vec<Tree>>, x: usize, y: usize) -> usize {
-    let ceiling = grid[y][x].height;
-    let l = score_direction(grid, ceiling, x, y, |x| x - 1, |y| y);
-    let r = score_direction(grid, ceiling, x, y, |x| x + 1, |y| y);
-    let u = score_direction(grid, ceiling, x, y, |x| x, |y| y - 1);
-    let d = score_direction(grid, ceiling, x, y, |x| x, |y| y + 1);
-    let score = l * r * u * d;
-    grid[y][x].view_score = score;
-    score
-}
-
-fn score_grid(grid: &mut Vec<Vec<Tree>>) {
-    let xmax = grid[0].len();
-    let ymax = grid.len();
-    for x in 0..xmax {
-        for y in 0..ymax {
-            score_grid_position(grid, x, y);
-        }
-    }
-}
-
-fn get_highest_view_score(input: &str) -> usize {
-    let mut grid = create_grid(input);
-    score_grid(&mut grid);
+    Ok(visible_trees_count)
+}
+
+fn get_highest_view_score(input: &str) -> Result<u128, GridError> {
+    let mut grid = create_grid(input)?;
+    analyse_grid(&mut grid, &Sight::default());
+    Ok(highest_view_score(&grid))
+}
+
+fn highest_view_score(grid: &[Vec<Tree>]) -> u128 {
     grid.iter()
-        .map(|i| i.iter().map(|j| j.view_score).max().unwrap())
+        .flatten()
+        .map(|t| t.view_score)
         .max()
-        .unwrap()
+        .unwrap_or(0)
 }
 
 fn main() {
@@ -223,12 +571,49 @@ fn main() {
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path).unwrap();
+    let mut file = File::open(path).unwrap();
     let mut input = String::new();
     file.read_to_string(&mut input).unwrap();
-    get_total_visible_trees(TEST_INPUT);
-    get_total_visible_trees(&input);
 
-    let highest_view_score = get_highest_view_score(&input);
-    println!("{}", highest_view_score);
+    // `diagonal` and `transparent=H` change how the trees see, and `csv=FIELD` or `pgm=FIELD`
+    // print the `visible` or `score` of every tree instead of the answers.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        let mut sight = Sight::default();
+        let mut output = None;
+        for arg in args.iter() {
+            match arg.split_once('=') {
+                None if arg == "diagonal" => sight.diagonals = true,
+                Some(("transparent", h)) => sight.transparent_below = h.parse().unwrap(),
+                Some((format @ ("csv" | "pgm"), "visible")) => {
+                    output = Some((format, Field::Visible))
+                }
+                Some((format @ ("csv" | "pgm"), "score")) => output = Some((format, Field::Score)),
+                _ => panic!("unknown argument {}", arg),
+            }
+        }
+        let mut grid = match create_grid(&input) {
+            Ok(grid) => grid,
+            Err(e) => {
+                println!("{:?}", e);
+                return;
+            }
+        };
+        analyse_grid(&mut grid, &sight);
+        match output {
+            Some(("csv", field)) => println!("{}", to_csv(&grid, field)),
+            Some((_, field)) => print!("{}", to_pgm(&grid, field)),
+            None => {
+                println!("{}", count_visible_trees(&grid));
+                println!("{}", highest_view_score(&grid));
+            }
+        }
+        return;
+    }
+
+    get_total_visible_trees(TEST_INPUT).unwrap();
+    match get_total_visible_trees(&input).and_then(|_| get_highest_view_score(&input)) {
+        Ok(highest_view_score) => println!("{}", highest_view_score),
+        Err(e) => println!("{:?}", e),
+    }
 }
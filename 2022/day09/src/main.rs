@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 #[cfg(test)]
 mod test {
@@ -15,8 +15,8 @@ R 2";
 
     #[test]
     fn find_visited_count_test() {
-        let (_, moves) = read_moves(TEST_INPUT_PART1).unwrap();
-        let visited_count = find_tail_visited_count::<2>(&moves);
+        let moves = parse_moves(TEST_INPUT_PART1).unwrap();
+        let visited_count = find_tail_visited_count(&moves, 2);
         assert_eq!(13, visited_count);
     }
 
@@ -30,10 +30,155 @@ L 25
 U 20";
     #[test]
     fn find_visited_count_test2() {
-        let (_, moves) = read_moves(TEST_INPUT_PART2).unwrap();
-        let visited_count = find_tail_visited_count::<10>(&moves);
+        let moves = parse_moves(TEST_INPUT_PART2).unwrap();
+        let visited_count = find_tail_visited_count(&moves, 10);
         assert_eq!(36, visited_count);
     }
+
+    #[test]
+    fn rope_paths_test() {
+        let moves = parse_moves(TEST_INPUT_PART1).unwrap();
+        let mut rope = Rope::new(2);
+        let steps = rope.steps(&moves).collect::<Vec<_>>();
+        assert_eq!(24, steps.len());
+        assert_eq!(
+            vec![Position { x: 1, y: 0, z: 0 }, Position::ORIGIN],
+            steps[0]
+        );
+        assert_eq!(
+            vec![Position { x: 4, y: 1, z: 0 }, Position { x: 3, y: 0, z: 0 }],
+            steps[4]
+        );
+        assert_eq!(25, rope.paths[1].len());
+        assert_eq!(steps[23][1], rope.paths[1][24]);
+        assert_eq!(13, rope.visited[1].len());
+        assert_eq!(rope.tail_visited(), rope.visited[1].len());
+        // The head goes further than the tail.
+        assert!(rope.visited[0].len() > rope.visited[1].len());
+
+        let mut rope = Rope::new(1);
+        rope.run(&moves);
+        assert_eq!(rope.visited[0].len(), rope.tail_visited());
+    }
+
+    #[test]
+    fn rope_render_test() {
+        let moves = parse_moves(TEST_INPUT_PART1).unwrap();
+        let mut rope = Rope::new(2);
+        rope.run(&moves);
+        assert_eq!(
+            "......
+......
+......
+......
+H.....",
+            rope.render(0)
+        );
+        assert_eq!(
+            "..##..
+...##.
+.TH##.
+....#.
+s###..",
+            rope.render(rope.len())
+        );
+        assert_eq!(rope.render(rope.len()), rope.render(100));
+
+        let moves = parse_moves("R 4\nU 4").unwrap();
+        let mut rope = Rope::new(10);
+        rope.run(&moves);
+        assert_eq!(
+            "....H
+....1
+..432
+.5...
+6....",
+            rope.render(8)
+        );
+    }
+
+    #[test]
+    fn default_motion_test() {
+        // The puzzle's rope is the default one.
+        for (input, knots, expected) in [(TEST_INPUT_PART1, 2, 13), (TEST_INPUT_PART2, 10, 36)] {
+            let moves = parse_moves(input).unwrap();
+            assert_eq!(
+                expected,
+                find_tail_visited_count_with_slack(&moves, knots, 1)
+            );
+            let mut rope = Rope::new(knots);
+            rope.run(&moves);
+            assert_eq!(expected, rope.tail_visited());
+        }
+    }
+
+    #[test]
+    fn invalid_move_test() {
+        for line in ["U ", "U x", "U -1", "U 2147483648", "X 1"] {
+            assert!(read_move(line).is_err(), "{:?}", line);
+        }
+        assert_eq!(i32::MAX, read_move("U 2147483647").unwrap().1.spaces);
+        assert_eq!(3, parse_moves("R 1\nU 2\nL 1\n\n").unwrap().len());
+        for (input, line, text) in [
+            ("R 1\nU x\nL 1", 2, "U x"),
+            ("R 1\n\nL 1", 2, ""),
+            ("R 1\nL 1 ", 2, "L 1 "),
+        ] {
+            assert_eq!(
+                Err(InputError::Move {
+                    line,
+                    text: text.to_string()
+                }),
+                parse_moves(input).map(|m| m.len())
+            );
+        }
+    }
+
+    #[test]
+    fn diagonal_moves_test() {
+        let moves = parse_moves("UR 3\nDL 1\nUL 2\nDR 2").unwrap();
+        assert_eq!(4, moves.len());
+        let mut rope = Rope::new(2);
+        let steps = rope.steps(&moves).collect::<Vec<_>>();
+        assert_eq!(Position { x: 3, y: 3, z: 0 }, steps[2][0]);
+        assert_eq!(Position { x: 2, y: 2, z: 0 }, steps[2][1]);
+        // Stepping back onto the tail leaves it where it is.
+        assert_eq!(Position { x: 2, y: 2, z: 0 }, steps[3][1]);
+        assert_eq!(Position { x: 0, y: 4, z: 0 }, steps[5][0]);
+        assert_eq!(Position { x: 1, y: 3, z: 0 }, steps[5][1]);
+        assert_eq!(4, rope.tail_visited());
+    }
+
+    #[test]
+    fn three_dimensions_test() {
+        let moves = parse_moves("F 3\nR 1\nB 5").unwrap();
+        let mut rope = Rope::new(3);
+        let steps = rope.steps(&moves).collect::<Vec<_>>();
+        assert_eq!(Position { x: 0, y: 0, z: 1 }, steps[2][2]);
+        assert_eq!(Position { x: 1, y: 0, z: -2 }, steps[8][0]);
+        assert_eq!(Position { x: 1, y: 0, z: -1 }, steps[8][1]);
+        assert_eq!(Position { x: 1, y: 0, z: 0 }, steps[8][2]);
+        assert_eq!(3, rope.tail_visited());
+    }
+
+    #[test]
+    fn slack_test() {
+        let moves = parse_moves(TEST_INPUT_PART1).unwrap();
+        // Without slack every knot follows the head exactly.
+        let mut rope = Rope::with_slack(3, 0);
+        rope.run(&moves);
+        assert_eq!(rope.visited[0], rope.visited[2]);
+        // More slack never visits more.
+        let counts = (0..5)
+            .map(|slack| find_tail_visited_count_with_slack(&moves, 2, slack))
+            .collect::<Vec<_>>();
+        assert!(counts.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(13, counts[1]);
+        let mut rope = Rope::with_slack(2, 3);
+        for knots in rope.steps(&moves) {
+            assert!(knots[0].distance(&knots[1]) <= 3);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -42,6 +187,32 @@ enum MoveDirection {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    /// Along the third axis, `F` and `B`.
+    Forward,
+    Back,
+}
+
+impl MoveDirection {
+    /// The change in position of one step.
+    fn delta(&self) -> Position {
+        let (x, y, z) = match self {
+            MoveDirection::Up => (0, 1, 0),
+            MoveDirection::Down => (0, -1, 0),
+            MoveDirection::Left => (-1, 0, 0),
+            MoveDirection::Right => (1, 0, 0),
+            MoveDirection::UpLeft => (-1, 1, 0),
+            MoveDirection::UpRight => (1, 1, 0),
+            MoveDirection::DownLeft => (-1, -1, 0),
+            MoveDirection::DownRight => (1, -1, 0),
+            MoveDirection::Forward => (0, 0, 1),
+            MoveDirection::Back => (0, 0, -1),
+        };
+        Position { x, y, z }
+    }
 }
 #[derive(Debug)]
 struct Move {
@@ -53,107 +224,311 @@ struct Move {
 struct Position {
     x: i32,
     y: i32,
+    z: i32,
+}
+
+impl Position {
+    const ORIGIN: Position = Position { x: 0, y: 0, z: 0 };
+
+    /// The number of king's moves between the positions, in up to three dimensions.
+    fn distance(&self, other: &Position) -> u32 {
+        self.x
+            .abs_diff(other.x)
+            .max(self.y.abs_diff(other.y))
+            .max(self.z.abs_diff(other.z))
+    }
 }
 
 fn read_move(input: &str) -> nom::IResult<&str, Move> {
-    use nom::bytes::complete::is_a;
-    use nom::character::complete::{char, digit0};
+    use nom::branch::alt;
+    use nom::bytes::complete::tag;
+    use nom::character::complete::{char, digit1};
+    use nom::combinator::map_res;
     use nom::sequence::separated_pair;
 
-    let (remaining, result) = separated_pair(is_a("LRUD"), char(' '), digit0)(input)?;
+    // The diagonals have to be tried before the single letters they start with.
+    let directions = alt((
+        tag("UL"),
+        tag("UR"),
+        tag("DL"),
+        tag("DR"),
+        tag("L"),
+        tag("R"),
+        tag("U"),
+        tag("D"),
+        tag("F"),
+        tag("B"),
+    ));
+    let spaces = map_res(digit1, str::parse::<i32>);
+    let (remaining, (direction, spaces)) = separated_pair(directions, char(' '), spaces)(input)?;
 
-    let direction = match result.0 {
+    let direction = match direction {
         "L" => MoveDirection::Left,
         "R" => MoveDirection::Right,
         "U" => MoveDirection::Up,
         "D" => MoveDirection::Down,
-        _ => panic!(),
+        "UL" => MoveDirection::UpLeft,
+        "UR" => MoveDirection::UpRight,
+        "DL" => MoveDirection::DownLeft,
+        "DR" => MoveDirection::DownRight,
+        "F" => MoveDirection::Forward,
+        "B" => MoveDirection::Back,
+        _ => unreachable!(),
     };
-    let spaces = result.1.parse::<i32>().unwrap();
-
     Ok((remaining, Move { direction, spaces }))
 }
 
-fn read_moves(input: &str) -> nom::IResult<&str, Vec<Move>> {
-    use nom::character::complete::newline;
-    use nom::multi::separated_list0;
-    let (remaining, result) = separated_list0(newline, read_move)(input)?;
+/// Something in the input or on the command line that can't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum InputError {
+    /// `line` is numbered from 1.
+    Move {
+        line: usize,
+        text: String,
+    },
+    Argument(String),
+}
 
-    Ok((remaining, result))
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::Move { line, text } => {
+                write!(f, "line {}: can't read move {:?}", line, text)
+            }
+            InputError::Argument(arg) => write!(f, "can't read argument {:?}", arg),
+        }
+    }
 }
 
-fn move_next_knot(rope: &mut [Position], ahead: usize, behind: usize) -> bool {
-    let mut moved = false;
-    if rope[ahead].x.abs_diff(rope[behind].x) > 1 || rope[ahead].y.abs_diff(rope[behind].y) > 1 {
-        let movex = rope[ahead].x - rope[behind].x;
-        let movex = if movex > 0 {
-            1
-        } else if movex < 0 {
-            -1
-        } else {
-            0
-        };
-        let movey = rope[ahead].y - rope[behind].y;
-        let movey = if movey > 0 {
-            1
-        } else if movey < 0 {
-            -1
-        } else {
-            0
+impl std::error::Error for InputError {}
+
+/// Reads every move, giving the line of the first one that can't be read. Blank lines are only
+/// allowed at the end.
+fn parse_moves(input: &str) -> Result<Vec<Move>, InputError> {
+    input
+        .trim_end_matches('\n')
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match read_move(line) {
+            Ok(("", m)) => Ok(m),
+            _ => Err(InputError::Move {
+                line: i + 1,
+                text: line.to_string(),
+            }),
+        })
+        .collect()
+}
+
+/// Moves the knot behind one space towards the knot ahead if they are more than `slack` apart.
+fn move_next_knot(rope: &mut [Position], ahead: usize, behind: usize, slack: u32) -> bool {
+    if rope[ahead].distance(&rope[behind]) <= slack {
+        return false;
+    }
+    rope[behind].x += (rope[ahead].x - rope[behind].x).signum();
+    rope[behind].y += (rope[ahead].y - rope[behind].y).signum();
+    rope[behind].z += (rope[ahead].z - rope[behind].z).signum();
+    true
+}
+/// A rope with any number of knots, the first being the head, that keeps the path of every
+/// knot.
+#[derive(Debug, Clone)]
+struct Rope {
+    knots: Vec<Position>,
+    /// The position of each knot before the first step and after every step since.
+    paths: Vec<Vec<Position>>,
+    visited: Vec<HashSet<Position>>,
+    /// How far apart neighbouring knots may be before the one behind moves.
+    slack: u32,
+}
+
+impl Rope {
+    /// Every knot starts at the origin, there has to be at least one.
+    fn new(knots: usize) -> Self {
+        Rope::with_slack(knots, 1)
+    }
+
+    /// A rope whose knots only have to stay within `slack` of each other, the puzzle uses 1.
+    fn with_slack(knots: usize, slack: u32) -> Self {
+        assert!(knots > 0, "a rope needs a head");
+        let start = Position::ORIGIN;
+        Rope {
+            knots: vec![start; knots],
+            paths: vec![vec![start]; knots],
+            visited: vec![HashSet::from([start]); knots],
+            slack,
+        }
+    }
+
+    /// Moves the head one space and lets the other knots follow.
+    fn step(&mut self, direction: &MoveDirection) {
+        let delta = direction.delta();
+        self.knots[0].x += delta.x;
+        self.knots[0].y += delta.y;
+        self.knots[0].z += delta.z;
+        for i in 0..self.knots.len() - 1 {
+            if !move_next_knot(&mut self.knots, i, i + 1, self.slack) {
+                break;
+            }
+        }
+        for (i, knot) in self.knots.iter().enumerate() {
+            self.paths[i].push(*knot);
+            self.visited[i].insert(*knot);
+        }
+    }
+
+    /// The knots after each single step of the moves.
+    fn steps<'a>(&'a mut self, moves: &'a [Move]) -> Steps<'a> {
+        Steps {
+            rope: self,
+            moves: moves.iter(),
+            current: None,
+        }
+    }
+
+    fn run(&mut self, moves: &[Move]) {
+        self.steps(moves).for_each(drop);
+    }
+
+    fn tail_visited(&self) -> usize {
+        self.visited[self.knots.len() - 1].len()
+    }
+
+    /// The number of steps taken so far.
+    fn len(&self) -> usize {
+        self.paths[0].len() - 1
+    }
+
+    /// Draws the rope as it was after `step` steps over the cells the tail had visited by then,
+    /// like the puzzle does. The drawing covers everywhere the rope goes over the whole run, a
+    /// rope moving in three dimensions is seen from above with the nearest knot drawn.
+    fn render(&self, step: usize) -> String {
+        let step = step.min(self.len());
+        let all = self.paths.iter().flatten();
+        let xmin = all.clone().map(|p| p.x).min().unwrap();
+        let xmax = all.clone().map(|p| p.x).max().unwrap();
+        let ymin = all.clone().map(|p| p.y).min().unwrap();
+        let ymax = all.map(|p| p.y).max().unwrap();
+        let tail = self.knots.len() - 1;
+        let visited = self.paths[tail][..=step]
+            .iter()
+            .map(|p| (p.x, p.y))
+            .collect::<HashSet<_>>();
+        let label = |knot: usize| match knot {
+            0 => 'H',
+            _ if tail == 1 => 'T',
+            _ => std::char::from_digit(knot as u32 % 36, 36).unwrap(),
         };
-        rope[behind].x += movex;
-        rope[behind].y += movey;
-        moved = (movex | movey) != 0;
-    }
-    moved
-}
-fn find_tail_visited_count<const N: usize>(moves: &Vec<Move>) -> usize {
-    let mut rope = [Position { x: 0, y: 0 }; N];
-
-    let mut hash_map = HashMap::<Position, &str>::new();
-
-    // store tail starting position
-    hash_map.insert(rope[N - 1], "value stored");
-
-    for m in moves {
-        let mut spaces = m.spaces;
-        while spaces > 0 {
-            match m.direction {
-                MoveDirection::Up => rope[0].y += 1,
-                MoveDirection::Down => rope[0].y -= 1,
-                MoveDirection::Right => rope[0].x += 1,
-                MoveDirection::Left => rope[0].x -= 1,
-                // _ => panic!(),
-            };
-            spaces -= 1;
-            for i in 0..N - 1 {
-                if !move_next_knot(&mut rope, i, i + 1) {
-                    break;
-                } else if i == N - 2 {
-                    // Tail has moved then store position
-                    hash_map.insert(rope[i + 1], "Some value");
+        (ymin..=ymax)
+            .rev()
+            .map(|y| {
+                (xmin..=xmax)
+                    .map(|x| {
+                        let here = |p: &Vec<Position>| p[step].x == x && p[step].y == y;
+                        match self.paths.iter().position(here) {
+                            Some(knot) => label(knot),
+                            None if x == 0 && y == 0 => 's',
+                            None if visited.contains(&(x, y)) => '#',
+                            None => '.',
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Takes the moves one space at a time, see `Rope::steps`.
+struct Steps<'a> {
+    rope: &'a mut Rope,
+    moves: std::slice::Iter<'a, Move>,
+    /// The move being made and how many spaces are left of it.
+    current: Option<(&'a Move, i32)>,
+}
+
+impl Iterator for Steps<'_> {
+    type Item = Vec<Position>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.current {
+                Some((m, spaces)) if spaces > 0 => {
+                    self.current = Some((m, spaces - 1));
+                    self.rope.step(&m.direction);
+                    return Some(self.rope.knots.clone());
+                }
+                _ => {
+                    let m = self.moves.next()?;
+                    self.current = Some((m, m.spaces));
                 }
             }
         }
     }
-    hash_map.len()
 }
 
-fn main() {
+fn find_tail_visited_count(moves: &[Move], knots: usize) -> usize {
+    find_tail_visited_count_with_slack(moves, knots, 1)
+}
+
+fn find_tail_visited_count_with_slack(moves: &[Move], knots: usize, slack: u32) -> usize {
+    let mut rope = Rope::with_slack(knots, slack);
+    rope.run(moves);
+    rope.tail_visited()
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     use std::fs::File;
     use std::io::Read;
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path).unwrap();
+    let mut file = File::open(path)?;
     let mut input = String::new();
-    file.read_to_string(&mut input).unwrap();
+    file.read_to_string(&mut input)?;
+
+    let moves = parse_moves(&input)?;
 
-    let (_, moves) = read_moves(&input).unwrap();
+    // `slack=K` lets the knots drift up to K apart, and `draw KNOTS [STEP]` draws the rope after
+    // STEP steps, or at the end.
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    fn number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, InputError> {
+        value
+            .parse()
+            .map_err(|_| InputError::Argument(arg.to_string()))
+    }
+    let slack: Option<u32> = match args.iter().position(|a| a.starts_with("slack=")) {
+        Some(i) => {
+            let arg = args.remove(i);
+            Some(number(&arg, &arg["slack=".len()..])?)
+        }
+        None => None,
+    };
+    if args.first().map(String::as_str) == Some("draw") {
+        let knots = args
+            .get(1)
+            .ok_or_else(|| InputError::Argument("draw".to_string()))?;
+        let knots = match number(knots, knots)? {
+            0 => return Err(InputError::Argument(knots.to_string()).into()),
+            knots => knots,
+        };
+        let mut rope = slack.map_or_else(|| Rope::new(knots), |k| Rope::with_slack(knots, k));
+        rope.run(&moves);
+        let step = match args.get(2) {
+            Some(step) => number(step, step)?,
+            None => rope.len(),
+        };
+        println!("{}", rope.render(step));
+        return Ok(());
+    }
 
-    let tail_visited = find_tail_visited_count::<2>(&moves);
+    let visited_count = |knots| match slack {
+        Some(slack) => find_tail_visited_count_with_slack(&moves, knots, slack),
+        None => find_tail_visited_count(&moves, knots),
+    };
+    let tail_visited = visited_count(2);
     println!("{}", tail_visited);
 
-    let tail_visited = find_tail_visited_count::<10>(&moves);
+    let tail_visited = visited_count(10);
     println!("{}", tail_visited);
+    Ok(())
 }
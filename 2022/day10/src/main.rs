@@ -1,3 +1,4 @@
+#[cfg(test)]
 const UNIT_TEST_INPUT: &str = "addx 15
 addx -11
 addx 6
@@ -148,16 +149,63 @@ noop";
 mod test {
     use super::*;
 
+    fn run_actions_for_part1(actions: &[Instruction]) -> Vec<Signal> {
+        Crt::default().sample(&run_actions(actions))
+    }
+
+    fn screen_for_part2(signals: &[Signal]) -> String {
+        Crt::default().render(signals)
+    }
+
     #[test]
     fn parse_line_test() {
         const LINE1: &str = "addx -32";
-        let (_, result) = parse_line(LINE1).unwrap();
-        assert_eq!(Action::AddX(-32), result);
+        let result = parse_line(LINE1).unwrap();
+        assert_eq!("addx", result.spec.opcode);
+        assert_eq!(vec![-32], result.operands);
+        assert_eq!(2, result.spec.cycles);
+
+        let result = parse_line("add y -4").unwrap();
+        assert_eq!(vec![1, -4], result.operands);
+    }
+
+    #[test]
+    fn parse_error_test() {
+        assert_eq!(
+            Err(VmError::UnknownOpcode {
+                line: 3,
+                opcode: "jmp".to_string()
+            }),
+            parse_lines("noop\naddx 3\njmp 4")
+        );
+        assert_eq!(
+            Err(VmError::Operands {
+                line: 1,
+                opcode: "addx".to_string(),
+                expected: 1,
+                found: 0
+            }),
+            parse_lines("addx")
+        );
+        assert_eq!(
+            Err(VmError::InvalidOperand {
+                line: 2,
+                text: "q".to_string()
+            }),
+            parse_lines("noop\nset q 1")
+        );
+        assert_eq!(
+            Err(VmError::InvalidOperand {
+                line: 1,
+                text: "x".to_string()
+            }),
+            parse_lines("addx x")
+        );
     }
 
     #[test]
     fn run_operations_test() {
-        let actions = parse_lines(UNIT_TEST_INPUT).unwrap().1;
+        let actions = parse_lines(UNIT_TEST_INPUT).unwrap();
         let register_log = run_actions_for_part1(&actions);
         assert_eq!(420, register_log[0].strength);
         assert_eq!(1140, register_log[1].strength);
@@ -166,129 +214,918 @@ mod test {
         assert_eq!(2880, register_log[4].strength);
         assert_eq!(3960, register_log[5].strength);
 
-        assert_eq!(13140, register_log.iter().map(|r| r.strength).sum::<i32>());
+        assert_eq!(13140, register_log.iter().map(|r| r.strength).sum::<i64>());
+    }
+
+    #[test]
+    fn screen_test() {
+        let actions = parse_lines(UNIT_TEST_INPUT).unwrap();
+        let screen = screen_for_part2(&run_actions(&actions));
+        assert_eq!(
+            "##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....",
+            screen
+        );
+        assert_eq!(
+            Err(OcrError::UnknownGlyph {
+                index: 0,
+                glyph: "##..#\n###..\n####.\n#####\n#####\n#####".to_string()
+            }),
+            read_screen(&screen)
+        );
+    }
+
+    #[test]
+    fn read_screen_test() {
+        const SCREEN: &str = "####.###..#..#.###..#..#.####..##..#..#.
+#....#..#.#..#.#..#.#..#....#.#..#.#..#.
+###..###..#..#.#..#.####...#..#....####.
+#....#..#.#..#.###..#..#..#...#....#..#.
+#....#..#.#..#.#.#..#..#.#....#..#.#..#.
+#....###...##..#..#.#..#.####..##..#..#.";
+        assert_eq!(Ok("FBURHZCH".to_string()), read_screen(SCREEN));
+        assert_eq!(Err(OcrError::Height(5)), read_screen(&SCREEN[41..]));
+        assert_eq!(
+            Ok("FBURHZCH".to_string()),
+            read_screen(&format!("{}\n", SCREEN))
+        );
+        assert_eq!(
+            Err(OcrError::BlankLine { line: 7 }),
+            read_screen(&format!("{}\n\n", SCREEN))
+        );
+
+        // A lit pixel between letters.
+        let mut lit_gap = SCREEN.to_string();
+        lit_gap.replace_range(4..5, "#");
+        assert_eq!(
+            Err(OcrError::UnknownGlyph {
+                index: 0,
+                glyph: "#####\n#....\n###..\n#....\n#....\n#....".to_string()
+            }),
+            read_screen(&lit_gap)
+        );
+
+        // Every letter of both fonts, the large one cropped after the last letter.
+        for font in [&SMALL_FONT, &LARGE_FONT] {
+            let letters = font.glyphs.iter().map(|(c, _)| *c).collect::<String>();
+            let blank = ".".repeat(font.pitch - font.width);
+            let screen = (0..font.height)
+                .map(|y| {
+                    let row = font
+                        .glyphs
+                        .iter()
+                        .map(|(_, rows)| rows[y])
+                        .collect::<Vec<_>>()
+                        .join(&blank);
+                    if font.height == SMALL_FONT.height {
+                        row + &blank
+                    } else {
+                        row
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert_eq!(Ok(letters), read_screen(&screen));
+        }
+    }
+
+    #[test]
+    fn crt_test() {
+        let actions = parse_lines(UNIT_TEST_INPUT).unwrap();
+        let trace = run_actions(&actions);
+        let crt = Crt::default();
+        assert_eq!(screen_for_part2(&trace), crt.render(&trace));
+        assert_eq!(
+            vec![20, 60, 100, 140, 180, 220],
+            crt.sample(&trace)
+                .iter()
+                .map(|s| s.cycle)
+                .collect::<Vec<_>>()
+        );
+
+        let crt = Crt::new(10, 2, 1, Schedule::At(vec![1, 5, 500])).unwrap();
+        assert_eq!(".#...#..#.\n....#.....", crt.render(&trace));
+        assert_eq!(
+            vec![1, 5],
+            crt.sample(&trace)
+                .iter()
+                .map(|s| s.cycle)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            ".#...#..#.  1 16 5 11 8\n....#.....  13 12 4 17 21",
+            crt.render_with_registers(&trace)
+        );
+
+        for (width, height) in [(0, 6), (40, 0)] {
+            assert_eq!(
+                Err(CrtError::Size { width, height }),
+                Crt::new(width, height, 3, Schedule::At(vec![]))
+            );
+        }
+
+        // A wider sprite, and a screen bigger than the trace.
+        let crt = Crt {
+            width: 4,
+            height: 3,
+            sprite: 4,
+            samples: Schedule::Every {
+                first: 3,
+                period: 2,
+            },
+        };
+        assert_eq!("##..\n....\n....", crt.render(&trace[..4]));
+        assert_eq!(
+            "##..  1 16\n....\n....",
+            crt.render_with_registers(&trace[..4])
+        );
+        assert_eq!(
+            vec![3],
+            crt.sample(&trace[..4])
+                .iter()
+                .map(|s| s.cycle)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn crt_png_test() {
+        let actions = parse_lines(UNIT_TEST_INPUT).unwrap();
+        let trace = run_actions(&actions);
+        let crt = Crt::default();
+        let mut image = Vec::new();
+        crt.write_png(&trace, 2, &mut image).unwrap();
+
+        let decoder = png::Decoder::new(&image[..]);
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((80, 12), (info.width, info.height));
+        // The first row starts `##..`, each pixel doubled.
+        assert_eq!([255, 255, 255, 255, 0, 0, 0, 0], data[..8]);
+        assert_eq!(data[..80], data[80..160]);
+
+        assert_eq!(
+            Err(CrtError::Scale(0)),
+            crt.write_png(&trace, 0, &mut Vec::new())
+        );
+        assert_eq!(
+            Err(CrtError::Scale(usize::MAX)),
+            crt.write_png(&trace, usize::MAX, &mut Vec::new())
+        );
+        assert_eq!(
+            Err(CrtError::Scale(1 << 30)),
+            crt.write_png(&trace, 1 << 30, &mut Vec::new())
+        );
+    }
+
+    #[test]
+    fn registers_test() {
+        let program = parse_lines("noop\naddx 3\naddx -5\nset y 7\nadd y 2\ncopy z y").unwrap();
+        let trace = run_actions(&program);
+        assert_eq!(
+            vec![1, 1, 1, 4, 4, -1, -1, -1, -1],
+            trace.iter().map(|s| s.register).collect::<Vec<_>>()
+        );
+        assert_eq!([-1, 7, 0, 0], trace[6].registers);
+        assert_eq!([-1, 9, 0, 0], trace[8].registers);
+        let mut cpu = Cpu::new(&program);
+        cpu.run();
+        assert_eq!([-1, 9, 9, 0], *cpu.registers());
+        assert_eq!(9, cpu.cycle());
+        assert!(cpu.step().is_none());
+    }
+
+    #[test]
+    fn overflow_test() {
+        // The noop is drawn with x wrapped around.
+        let program = parse_lines("addx 2147483647\nnoop").unwrap();
+        let mut cpu = Cpu::new(&program);
+        assert_eq!(Stop::Halted, cpu.run());
+        assert_eq!(i32::MIN, cpu.registers()[0]);
+        let screen = Crt::default().render(cpu.trace());
+        assert!(screen.starts_with("##.."));
+        assert_eq!(2, screen.matches('#').count());
+
+        let program = parse_lines(&format!("addx 100000000\n{}", "noop\n".repeat(20))).unwrap();
+        let trace = run_actions(&program);
+        assert_eq!(2_000_000_020, trace[19].strength);
+        assert_eq!(2_200_000_022, trace[21].strength);
+
+        let program = parse_lines("set y 2147483647\nadd y 2147483647").unwrap();
+        let mut cpu = Cpu::new(&program);
+        cpu.run();
+        assert_eq!(-2, cpu.registers()[1]);
+    }
+
+    #[test]
+    fn debugger_test() {
+        let program = parse_lines(UNIT_TEST_INPUT).unwrap();
+        let mut cpu = Cpu::new(&program);
+        cpu.add_breakpoint(Breakpoint::Cycle(20)).unwrap();
+        cpu.add_breakpoint(Breakpoint::Register {
+            register: 0,
+            value: 21,
+        })
+        .unwrap();
+        assert_eq!(
+            Err(VmError::UnknownRegister { register: 7 }),
+            cpu.add_breakpoint(Breakpoint::Register {
+                register: 7,
+                value: 0
+            })
+        );
+
+        // x becomes 21 at the end of cycle 18.
+        assert_eq!(
+            Stop::Breakpoint(Breakpoint::Register {
+                register: 0,
+                value: 21
+            }),
+            cpu.run()
+        );
+        assert_eq!(18, cpu.cycle());
+        assert_eq!(17, cpu.trace()[17].register);
+        assert_eq!(21, cpu.registers()[0]);
+
+        assert_eq!(Stop::Breakpoint(Breakpoint::Cycle(20)), cpu.run());
+        assert_eq!(20, cpu.cycle());
+        assert_eq!(21, cpu.trace()[19].register);
+        assert_eq!(420, cpu.trace()[19].strength);
+
+        let signal = cpu.step().unwrap();
+        assert_eq!(21, signal.cycle);
+        assert_eq!(Some(&signal), cpu.trace().last());
+
+        let mut stops = Vec::new();
+        loop {
+            match cpu.run() {
+                Stop::Halted => break,
+                Stop::Breakpoint(_) => stops.push(cpu.registers()[0]),
+            }
+        }
+        assert!(stops.iter().all(|x| *x == 21));
+        assert_eq!(240, cpu.cycle());
+        assert_eq!(240, cpu.trace().len());
+        assert_eq!(run_actions(&program), cpu.trace());
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum Action {
-    NoOp,
-    AddX(i32),
+/// The names of the registers, `x` is the one the puzzle uses.
+const REGISTERS: [&str; 4] = ["x", "y", "z", "w"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    /// The index of one of the `REGISTERS`.
+    Register,
+    Immediate,
 }
 
-struct Signal {
-    register: i32,
-    cycle: i32,
-    strength: i32,
+/// An entry of the instruction table.
+#[derive(Debug)]
+struct Spec {
+    opcode: &'static str,
+    operands: &'static [Operand],
+    /// How many cycles the instruction takes, it takes effect at the end of the last one.
+    cycles: usize,
+    execute: fn(&mut [i32; REGISTERS.len()], &[i32]),
 }
 
-fn parse_line(input: &str) -> nom::IResult<&str, Action> {
-    use nom::branch::alt;
-    use nom::bytes::complete::tag;
-    use nom::character::complete::char;
-    use nom::character::complete::not_line_ending;
-    use nom::sequence::preceded;
+/// The instructions the CPU understands, new ones only need an entry here. Registers wrap around
+/// like a real 32 bit CPU's rather than overflowing.
+const INSTRUCTIONS: &[Spec] = &[
+    Spec {
+        opcode: "noop",
+        operands: &[],
+        cycles: 1,
+        execute: |_, _| {},
+    },
+    Spec {
+        opcode: "addx",
+        operands: &[Operand::Immediate],
+        cycles: 2,
+        execute: |registers, operands| registers[0] = registers[0].wrapping_add(operands[0]),
+    },
+    Spec {
+        opcode: "add",
+        operands: &[Operand::Register, Operand::Immediate],
+        cycles: 2,
+        execute: |registers, operands| {
+            let register = &mut registers[operands[0] as usize];
+            *register = register.wrapping_add(operands[1])
+        },
+    },
+    Spec {
+        opcode: "set",
+        operands: &[Operand::Register, Operand::Immediate],
+        cycles: 1,
+        execute: |registers, operands| registers[operands[0] as usize] = operands[1],
+    },
+    Spec {
+        opcode: "copy",
+        operands: &[Operand::Register, Operand::Register],
+        cycles: 1,
+        execute: |registers, operands| {
+            registers[operands[0] as usize] = registers[operands[1] as usize]
+        },
+    },
+];
 
-    let (mut remaining, output) = alt((tag("addx"), tag("noop")))(input)?;
-    let mut action = Action::NoOp;
-    if output == "addx" {
-        let (remains, number) = preceded(char(' '), not_line_ending)(remaining)?;
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum VmError {
+    /// `line` is numbered from 1.
+    UnknownOpcode { line: usize, opcode: String },
+    Operands {
+        line: usize,
+        opcode: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Not a number or a register, whichever the instruction takes there.
+    InvalidOperand { line: usize, text: String },
+    /// A breakpoint on a register that isn't one of the `REGISTERS`.
+    UnknownRegister { register: usize },
+}
 
-        action = Action::AddX(number.parse::<i32>().unwrap());
-        remaining = remains;
+impl VmError {
+    fn at_line(self, line: usize) -> Self {
+        match self {
+            VmError::UnknownOpcode { opcode, .. } => VmError::UnknownOpcode { line, opcode },
+            VmError::Operands {
+                opcode,
+                expected,
+                found,
+                ..
+            } => VmError::Operands {
+                line,
+                opcode,
+                expected,
+                found,
+            },
+            VmError::InvalidOperand { text, .. } => VmError::InvalidOperand { line, text },
+            VmError::UnknownRegister { .. } => self,
+        }
     }
+}
 
-    Ok((remaining, action))
+#[derive(Debug, PartialEq)]
+struct Instruction {
+    spec: &'static Spec,
+    /// Registers are given by their index.
+    operands: Vec<i32>,
 }
 
-fn parse_lines(input: &str) -> nom::IResult<&str, Vec<Action>> {
-    use nom::character::complete::newline;
-    use nom::multi::separated_list0;
-    let (remaining, result) = separated_list0(newline, parse_line)(input)?;
+impl PartialEq for Spec {
+    fn eq(&self, other: &Self) -> bool {
+        self.opcode == other.opcode
+    }
+}
 
-    Ok((remaining, result))
+#[derive(Debug, Clone, PartialEq)]
+struct Signal {
+    /// The value of `x` during the cycle.
+    register: i32,
+    cycle: usize,
+    /// Wide enough for any value of `x` times any cycle a trace could hold.
+    strength: i64,
+    registers: [i32; REGISTERS.len()],
 }
 
-fn run_actions_for_part1(actions: &Vec<Action>) -> Vec<Signal> {
-    run_actions(actions)
-        .into_iter()
-        .enumerate()
-        .filter(|(i, _)| {
-            let i = *i + 1;
-            (i + 20) % 40 == 0
+fn parse_line(input: &str) -> Result<Instruction, VmError> {
+    let mut words = input.split_whitespace();
+    let opcode = words.next().unwrap_or_default();
+    let spec = INSTRUCTIONS
+        .iter()
+        .find(|spec| spec.opcode == opcode)
+        .ok_or_else(|| VmError::UnknownOpcode {
+            line: 1,
+            opcode: opcode.to_string(),
+        })?;
+    let words = words.collect::<Vec<_>>();
+    if words.len() != spec.operands.len() {
+        return Err(VmError::Operands {
+            line: 1,
+            opcode: opcode.to_string(),
+            expected: spec.operands.len(),
+            found: words.len(),
+        });
+    }
+    let operands = spec
+        .operands
+        .iter()
+        .zip(words)
+        .map(|(operand, word)| {
+            match operand {
+                Operand::Register => REGISTERS.iter().position(|r| *r == word).map(|r| r as i32),
+                Operand::Immediate => word.parse().ok(),
+            }
+            .ok_or_else(|| VmError::InvalidOperand {
+                line: 1,
+                text: word.to_string(),
+            })
         })
-        .map(|(_, v)| v)
-        .collect::<Vec<Signal>>()
+        .collect::<Result<_, _>>()?;
+
+    Ok(Instruction { spec, operands })
 }
 
-fn run_actions(actions: &Vec<Action>) -> Vec<Signal> {
-    const NO_OP: Action = Action::NoOp;
+fn parse_lines(input: &str) -> Result<Vec<Instruction>, VmError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_line(line).map_err(|e| e.at_line(i + 1)))
+        .collect()
+}
 
-    let mut results = Vec::<Signal>::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breakpoint {
+    /// Stops once the cycle has run.
+    Cycle(usize),
+    /// Stops when an instruction sets the register to the value from something else.
+    Register { register: usize, value: i32 },
+}
 
-    let mut cycle = 1i32;
-    let mut register = 1i32;
-    let mut actioncountdown = 0;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    Breakpoint(Breakpoint),
+    /// The program has finished.
+    Halted,
+}
 
-    let mut action: &Action = &NO_OP;
-    let mut iterator = actions.iter();
-    loop {
-        if actioncountdown == 0 {
-            if let Action::AddX(a) = action {
-                register += *a;
+/// Runs a program a cycle at a time, keeping a trace of every cycle.
+struct Cpu<'a> {
+    program: &'a [Instruction],
+    /// The instruction being run.
+    pc: usize,
+    /// How many cycles of it are left.
+    remaining: usize,
+    registers: [i32; REGISTERS.len()],
+    breakpoints: Vec<Breakpoint>,
+    trace: Vec<Signal>,
+}
+
+impl<'a> Cpu<'a> {
+    /// `x` starts at 1 and the other registers at 0.
+    fn new(program: &'a [Instruction]) -> Self {
+        let mut registers = [0; REGISTERS.len()];
+        registers[0] = 1;
+        Cpu {
+            program,
+            pc: 0,
+            remaining: 0,
+            registers,
+            breakpoints: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> Result<(), VmError> {
+        if let Breakpoint::Register { register, .. } = breakpoint {
+            if register >= REGISTERS.len() {
+                return Err(VmError::UnknownRegister { register });
             }
         }
-        results.push(Signal {
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    fn registers(&self) -> &[i32; REGISTERS.len()] {
+        &self.registers
+    }
+
+    /// The number of cycles run so far.
+    fn cycle(&self) -> usize {
+        self.trace.len()
+    }
+
+    fn trace(&self) -> &[Signal] {
+        &self.trace
+    }
+
+    /// Runs a single cycle, giving what the registers were during it, or `None` once the
+    /// program has finished.
+    fn step(&mut self) -> Option<Signal> {
+        let instruction = self.program.get(self.pc)?;
+        if self.remaining == 0 {
+            self.remaining = instruction.spec.cycles;
+        }
+        let cycle = self.cycle() + 1;
+        let signal = Signal {
+            register: self.registers[0],
             cycle,
-            register,
-            strength: register * cycle,
-        });
-        if actioncountdown == 0 {
-            let possible_action = iterator.next();
-            if possible_action.is_none() {
-                break;
+            strength: i64::from(self.registers[0]) * cycle as i64,
+            registers: self.registers,
+        };
+        self.trace.push(signal.clone());
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            (instruction.spec.execute)(&mut self.registers, &instruction.operands);
+            self.pc += 1;
+        }
+        Some(signal)
+    }
+
+    /// Runs until a breakpoint is hit, always running at least one cycle.
+    fn run(&mut self) -> Stop {
+        loop {
+            let before = self.registers;
+            if self.step().is_none() {
+                return Stop::Halted;
             }
-            action = possible_action.unwrap();
-            (cycle, register) = match action {
-                Action::NoOp => (cycle, register),
-                Action::AddX(_) => {
-                    actioncountdown = 1;
-                    (cycle, register)
+            let hit = self.breakpoints.iter().find(|b| match **b {
+                Breakpoint::Cycle(cycle) => cycle == self.cycle(),
+                Breakpoint::Register { register, value } => {
+                    before[register] != value && self.registers[register] == value
                 }
+            });
+            if let Some(hit) = hit {
+                return Stop::Breakpoint(*hit);
             }
-        } else {
-            actioncountdown -= 1;
         }
-        cycle += 1;
     }
+}
 
-    results
+fn run_actions(actions: &[Instruction]) -> Vec<Signal> {
+    let mut cpu = Cpu::new(actions);
+    cpu.run();
+    cpu.trace().to_vec()
 }
 
-fn screen_for_part2(signals: &Vec<Signal>) -> String {
-    let mut screen = String::with_capacity(250);
+/// When the signal strength is sampled, by cycle numbered from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Schedule {
+    Every { first: usize, period: usize },
+    At(Vec<usize>),
+}
 
-    for (i, v) in signals.iter().enumerate() {
-        let mut to_draw = false;
-        let i = i as i32;
-        if i != 0 && i % 40 == 0 {
-            screen.push_str("\n");
-        }
-        let i = i % 40;
-        println!("{} - {}", i + 1, v.register);
-        for j in i - 1..=i + 1 {
-            if j as i32 == v.register {
-                to_draw = true;
+impl Schedule {
+    fn contains(&self, cycle: usize) -> bool {
+        match self {
+            Schedule::Every { first, period } => {
+                cycle >= *first && (cycle - first).is_multiple_of(*period)
             }
+            Schedule::At(cycles) => cycles.contains(&cycle),
         }
-        if to_draw {
-            screen.push('#');
-        } else {
-            screen.push('.');
+    }
+}
+
+/// The screen the CPU drives, drawing a pixel a cycle from the top left while the sprite is
+/// centred on `x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Crt {
+    width: usize,
+    height: usize,
+    /// How many pixels wide the sprite is, an even width has its extra pixel on the right.
+    sprite: usize,
+    samples: Schedule,
+}
+
+impl Default for Crt {
+    /// The puzzle's screen.
+    fn default() -> Self {
+        Crt {
+            width: 40,
+            height: 6,
+            sprite: 3,
+            samples: Schedule::Every {
+                first: 20,
+                period: 40,
+            },
         }
     }
+}
 
-    screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CrtError {
+    /// The screen needs at least one pixel each way.
+    Size {
+        width: usize,
+        height: usize,
+    },
+    /// Drawing nothing, or an image too big for a PNG.
+    Scale(usize),
+    Png(String),
+}
+
+impl Crt {
+    fn new(
+        width: usize,
+        height: usize,
+        sprite: usize,
+        samples: Schedule,
+    ) -> Result<Self, CrtError> {
+        if width == 0 || height == 0 {
+            return Err(CrtError::Size { width, height });
+        }
+        Ok(Crt {
+            width,
+            height,
+            sprite,
+            samples,
+        })
+    }
+
+    fn sample(&self, trace: &[Signal]) -> Vec<Signal> {
+        trace
+            .iter()
+            .filter(|signal| self.samples.contains(signal.cycle))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether each pixel is lit, by row. The screen stays dark past the end of the trace and
+    /// anything after the screen is full is ignored.
+    fn pixels(&self, trace: &[Signal]) -> Vec<Vec<bool>> {
+        // In i64 so that a sprite at the far end of the register's range still fits.
+        let left = (self.sprite as i64 - 1) / 2;
+        (0..self.height)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| {
+                        trace.get(y * self.width + x).is_some_and(|signal| {
+                            let start = i64::from(signal.register) - left;
+                            (start..start + self.sprite as i64).contains(&(x as i64))
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn render(&self, trace: &[Signal]) -> String {
+        self.pixels(trace)
+            .iter()
+            .map(|row| row.iter().map(|lit| if *lit { '#' } else { '.' }).collect())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// The screen with the values `x` went through while drawing each row next to it.
+    fn render_with_registers(&self, trace: &[Signal]) -> String {
+        self.render(trace)
+            .lines()
+            .zip(trace.chunks(self.width).chain(std::iter::repeat(&[][..])))
+            .map(|(row, signals)| {
+                let mut values = signals.iter().map(|s| s.register).collect::<Vec<_>>();
+                values.dedup();
+                let values = values.iter().map(i32::to_string).collect::<Vec<_>>();
+                format!("{}  {}", row, values.join(" "))
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Writes a greyscale PNG with every pixel drawn `scale` times as wide and high.
+    fn write_png<W: std::io::Write>(
+        &self,
+        trace: &[Signal],
+        scale: usize,
+        writer: W,
+    ) -> Result<(), CrtError> {
+        let scaled = |size: usize| {
+            size.checked_mul(scale)
+                .and_then(|size| u32::try_from(size).ok())
+                .filter(|size| *size > 0)
+                .ok_or(CrtError::Scale(scale))
+        };
+        let mut encoder = png::Encoder::new(writer, scaled(self.width)?, scaled(self.height)?);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data = self
+            .pixels(trace)
+            .iter()
+            .flat_map(|row| {
+                let row = row
+                    .iter()
+                    .flat_map(|lit| std::iter::repeat_n(if *lit { 255 } else { 0 }, scale))
+                    .collect::<Vec<u8>>();
+                std::iter::repeat_n(row, scale).flatten()
+            })
+            .collect::<Vec<_>>();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&data))
+            .map_err(|e| CrtError::Png(e.to_string()))
+    }
+}
+
+/// A fixed pitch font of `#` and `.` glyphs.
+struct Font {
+    width: usize,
+    height: usize,
+    /// The glyph width plus the blank columns between glyphs.
+    pitch: usize,
+    glyphs: &'static [(char, &'static [&'static str])],
+}
+
+/// The letters the CRT puzzles show.
+const SMALL_FONT: Font = Font {
+    width: 4,
+    height: 6,
+    pitch: 5,
+    glyphs: &[
+        ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', &[".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ],
+};
+
+/// The taller letters some other years' puzzles use.
+const LARGE_FONT: Font = Font {
+    width: 6,
+    height: 10,
+    pitch: 8,
+    glyphs: &[
+        (
+            'A',
+            &[
+                "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'B',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+                "#....#", "#####.",
+            ],
+        ),
+        (
+            'C',
+            &[
+                ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+                "#....#", ".####.",
+            ],
+        ),
+        (
+            'E',
+            &[
+                "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+        (
+            'F',
+            &[
+                "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "#.....",
+            ],
+        ),
+        (
+            'G',
+            &[
+                ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+                "#...##", ".###.#",
+            ],
+        ),
+        (
+            'H',
+            &[
+                "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'J',
+            &[
+                "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+                "#...#.", ".###..",
+            ],
+        ),
+        (
+            'K',
+            &[
+                "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+                "#...#.", "#....#",
+            ],
+        ),
+        (
+            'L',
+            &[
+                "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+        (
+            'N',
+            &[
+                "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+                "#...##", "#....#",
+            ],
+        ),
+        (
+            'P',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+                "#.....", "#.....",
+            ],
+        ),
+        (
+            'R',
+            &[
+                "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'X',
+            &[
+                "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+                "#....#", "#....#",
+            ],
+        ),
+        (
+            'Z',
+            &[
+                "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+                "#.....", "######",
+            ],
+        ),
+    ],
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OcrError {
+    /// Neither font is this tall.
+    Height(usize),
+    /// `index` counts glyphs from 0, `glyph` is its rows joined by newlines, taking in the blank
+    /// columns after it if any of them are lit.
+    UnknownGlyph { index: usize, glyph: String },
+    /// An empty row, such as a blank line at the end. `line` is numbered from 1.
+    BlankLine { line: usize },
+}
+
+/// Reads the letters off a screen, choosing the font by the screen's height. Anything but `#`
+/// is taken to be dark.
+fn read_screen(screen: &str) -> Result<String, OcrError> {
+    let rows = screen
+        .lines()
+        .map(|row| row.chars().map(|c| c == '#').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    if let Some(i) = rows.iter().position(Vec::is_empty) {
+        return Err(OcrError::BlankLine { line: i + 1 });
+    }
+    let font = [&SMALL_FONT, &LARGE_FONT]
+        .into_iter()
+        .find(|font| font.height == rows.len())
+        .ok_or(OcrError::Height(rows.len()))?;
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    // The last glyph need not be followed by blank columns.
+    let count = columns.div_ceil(font.pitch);
+    (0..count)
+        .map(|index| {
+            let start = index * font.pitch;
+            let columns = |end: usize| {
+                rows.iter()
+                    .map(|row| {
+                        (start..end)
+                            .map(|x| if row.get(x) == Some(&true) { '#' } else { '.' })
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let gap_lit = rows.iter().any(|row| {
+                row.iter()
+                    .take(start + font.pitch)
+                    .skip(start + font.width)
+                    .any(|lit| *lit)
+            });
+            let glyph = columns(start + font.width);
+            font.glyphs
+                .iter()
+                .find(|(_, rows)| !gap_lit && rows.iter().eq(&glyph))
+                .map(|(letter, _)| *letter)
+                .ok_or_else(|| OcrError::UnknownGlyph {
+                    index,
+                    glyph: if gap_lit {
+                        columns(start + font.pitch).join("\n")
+                    } else {
+                        glyph.join("\n")
+                    },
+                })
+        })
+        .collect()
 }
 
 fn main() {
@@ -297,16 +1134,102 @@ fn main() {
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path).unwrap();
+    let mut file = File::open(path).unwrap();
     let mut input = String::new();
     file.read_to_string(&mut input).unwrap();
 
-    let actions = parse_lines(&input).unwrap().1;
-    let results = run_actions_for_part1(&actions);
-    println!("{}", results.iter().map(|m| m.strength).sum::<i32>());
-    // let actions = parse_lines(UNIT_TEST_INPUT).unwrap().1;
+    let actions = parse_lines(&input).unwrap();
+
+    // `trace` prints the registers during every cycle, and `debug` takes breakpoints like
+    // `cycle=N` or `x=V` and prints the registers at each one.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let show = |cycle: usize, registers: &[i32; REGISTERS.len()]| {
+        let registers = REGISTERS
+            .iter()
+            .zip(registers)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>();
+        println!("{} {}", cycle, registers.join(" "));
+    };
+    match args.first().map(String::as_str) {
+        Some("trace") => {
+            for signal in run_actions(&actions) {
+                show(signal.cycle, &signal.registers);
+            }
+            return;
+        }
+        Some("debug") => {
+            let mut cpu = Cpu::new(&actions);
+            for arg in &args[1..] {
+                let (name, value) = arg.split_once('=').unwrap();
+                let breakpoint = match REGISTERS.iter().position(|r| *r == name) {
+                    Some(register) => Breakpoint::Register {
+                        register,
+                        value: value.parse().unwrap(),
+                    },
+                    None if name == "cycle" => Breakpoint::Cycle(value.parse().unwrap()),
+                    None => panic!("unknown breakpoint {}", arg),
+                };
+                cpu.add_breakpoint(breakpoint).unwrap();
+            }
+            while let Stop::Breakpoint(breakpoint) = cpu.run() {
+                print!("{:?} after ", breakpoint);
+                show(cpu.cycle(), cpu.registers());
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    // The screen is set up with `width=`, `height=`, `sprite=` and `samples=`, taking either a
+    // list like `20,60` or `FIRST+PERIOD`. `side` shows `x` next to each row and `png=FILE`
+    // saves the screen too, with `scale=`.
+    let Crt {
+        mut width,
+        mut height,
+        mut sprite,
+        mut samples,
+    } = Crt::default();
+    let mut side = false;
+    let mut png = None;
+    let mut scale = 8;
+    for arg in &args {
+        match arg.split_once('=') {
+            Some(("width", value)) => width = value.parse().unwrap(),
+            Some(("height", value)) => height = value.parse().unwrap(),
+            Some(("sprite", value)) => sprite = value.parse().unwrap(),
+            Some(("samples", value)) => {
+                samples = match value.split_once('+') {
+                    Some((first, period)) => Schedule::Every {
+                        first: first.parse().unwrap(),
+                        period: period.parse().unwrap(),
+                    },
+                    None => Schedule::At(value.split(',').map(|c| c.parse().unwrap()).collect()),
+                }
+            }
+            Some(("png", value)) => png = Some(value.to_string()),
+            Some(("scale", value)) => scale = value.parse().unwrap(),
+            None if arg == "side" => side = true,
+            _ => panic!("unknown option {}", arg),
+        }
+    }
+    let crt = Crt::new(width, height, sprite, samples).unwrap();
+
     let results = run_actions(&actions);
-    // let screen = screen_for_part2(&results);
-    let screen = screen_for_part2(&results);
-    println!("{}", screen);
+    let samples = crt.sample(&results);
+    println!("{}", samples.iter().map(|m| m.strength).sum::<i64>());
+    let screen = crt.render(&results);
+    if side {
+        println!("{}", crt.render_with_registers(&results));
+    } else {
+        println!("{}", screen);
+    }
+    if let Some(png) = png {
+        let file = std::io::BufWriter::new(File::create(png).unwrap());
+        crt.write_png(&results, scale, file).unwrap();
+    }
+    match read_screen(&screen) {
+        Ok(letters) => println!("{}", letters),
+        Err(e) => println!("{:?}", e),
+    }
 }
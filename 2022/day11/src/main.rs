@@ -1,19 +1,15 @@
+#[derive(Debug, PartialEq)]
 struct Monkey {
     id: usize,
     divisible_by: u128,
     iftrue: usize,
     iffalse: usize,
-    worry_calculation: fn(old: u128, argument: u128) -> u128,
-    worry_argument: WorryArgument,
+    /// How the worry level changes when the monkey inspects an item.
+    operation: Expr,
     items: Vec<u128>,
     inspected: u64,
 }
 
-enum WorryArgument {
-    Old,
-    Constant(u128),
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
@@ -60,44 +56,264 @@ Monkey 3:
 
     #[test]
     fn parse_monkeys_test() {
-        let (_, monkeys) = parse_monkeys(TEST_INPUT2).unwrap();
+        let monkeys = parse_monkeys(TEST_INPUT2).unwrap();
+        assert_eq!(4, monkeys.len());
+        assert_eq!(
+            Expr::binary('*', Expr::Old, Expr::Old),
+            monkeys[2].operation
+        );
     }
 
     #[test]
-    fn calculate_worry_test() {
-        let (_, mut monkeys) = parse_monkeys(TEST_INPUT2).unwrap();
+    fn parse_expression_test() {
+        let (rest, expr) = parse_expression("old * old + 3 * (old - 1)").unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(Some(37), expr.eval(5));
+        assert_eq!(Some(2), expr.eval_mod(5, 5));
+
+        // Left associative.
+        let (_, expr) = parse_expression("10 - 4 - 3").unwrap();
+        assert_eq!(Some(3), expr.eval(0));
+        let (_, expr) = parse_expression("(old+1)*(old+2)").unwrap();
+        assert_eq!(Some(30), expr.eval(4));
+
+        let (_, expr) = parse_expression("old - 10").unwrap();
+        assert_eq!(None, expr.eval(3));
+        // Modulo 7, 3 - 10 wraps around to 0.
+        assert_eq!(Some(0), expr.eval_mod(3, 7));
+
+        let (_, expr) = parse_expression("old / (old - old)").unwrap();
+        assert!(expr.has_division());
+        assert_eq!(None, expr.eval(3));
+        assert_eq!(None, expr.eval_mod(3, 7));
+    }
 
-        calculate_worry_part1(&mut monkeys);
-        calculate_worry_part1(&mut monkeys);
-        assert_eq!(0, monkeys[2].items.len());
-        assert_eq!(0, monkeys[3].items.len());
+    #[test]
+    fn modular_matches_exact_test() {
+        let (_, expr) = parse_expression("old * (old + 7) - 2 * old + 19").unwrap();
+        for old in 1..200 {
+            assert_eq!(
+                expr.eval(old).map(|worry| worry % 96577),
+                expr.eval_mod(old, 96577)
+            );
+        }
+    }
 
-        dbg!(monkeys[0].items.clone());
+    #[test]
+    fn parse_monkeys_error_test() {
+        let input = TEST_INPUT2.replace("new = old * old", "new = old ** 2");
+        assert_eq!(
+            Err(MonkeyError::Parse {
+                monkey: 2,
+                line: "  Operation: new = old ** 2".to_string()
+            }),
+            parse_monkeys(&input)
+        );
+        let input = TEST_INPUT2.replace("Monkey 3:", "Monkey three:");
         assert_eq!(
-            true,
-            monkeys[0]
-                .items
-                .clone()
-                .into_iter()
-                .find(|u| *u == 695u128)
-                .is_some()
+            Err(MonkeyError::Parse {
+                monkey: 3,
+                line: "Monkey three:".to_string()
+            }),
+            parse_monkeys(&input)
         );
+        let input = TEST_INPUT2.replace("old + 6", "(old + 6) / 2");
         assert_eq!(
-            true,
-            monkeys[0]
-                .items
-                .clone()
-                .into_iter()
-                .find(|u| *u == 10u128)
-                .is_some()
+            Err(MonkeyError::Division { monkey: 1 }),
+            parse_monkeys(&input)
         );
     }
+
+    #[test]
+    fn invalid_monkeys_test() {
+        let input = TEST_INPUT2.replace("by 13", "by 0");
+        assert_eq!(
+            Err(MonkeyError::ZeroDivisor { monkey: 2 }),
+            parse_monkeys(&input)
+        );
+        let input =
+            TEST_INPUT2.replace("If false: throw to monkey 0", "If false: throw to monkey 5");
+        assert_eq!(
+            Err(MonkeyError::Target {
+                monkey: 1,
+                target: 5
+            }),
+            parse_monkeys(&input)
+        );
+        let input = TEST_INPUT2.replace("Monkey 3", "Monkey 99999999999999999999999");
+        assert_eq!(
+            Err(MonkeyError::Parse {
+                monkey: 3,
+                line: "Monkey 99999999999999999999999:".to_string()
+            }),
+            parse_monkeys(&input)
+        );
+        // Ids out of order, skipped or repeated.
+        let input = TEST_INPUT2
+            .replace("Monkey 1:", "Monkey x:")
+            .replace("Monkey 2:", "Monkey 1:")
+            .replace("Monkey x:", "Monkey 2:");
+        assert_eq!(
+            Err(MonkeyError::Parse {
+                monkey: 2,
+                line: "Monkey 2:".to_string()
+            }),
+            parse_monkeys(&input)
+        );
+        for header in ["Monkey 4:", "Monkey 2:"] {
+            let input = TEST_INPUT2.replace("Monkey 3:", header);
+            assert_eq!(
+                Err(MonkeyError::Parse {
+                    monkey: header[7..8].parse().unwrap(),
+                    line: header.to_string()
+                }),
+                parse_monkeys(&input)
+            );
+        }
+        let input = TEST_INPUT2.replace("Starting items: 74", "Starting items: ");
+        assert_eq!(Vec::<u128>::new(), parse_monkeys(&input).unwrap()[3].items);
+
+        // Blank blocks at the end, or between monkeys.
+        for input in [
+            format!("{}\n\n", TEST_INPUT2),
+            format!("{}\n\n\n", TEST_INPUT2),
+            TEST_INPUT2.replace("\n\nMonkey 2", "\n\n  \n\nMonkey 2"),
+        ] {
+            assert_eq!(4, parse_monkeys(&input).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn overflow_test() {
+        let input = TEST_INPUT2.replace("old * 19", "old - 80");
+        let mut monkeys = parse_monkeys(&input).unwrap();
+        assert_eq!(
+            Err(MonkeyError::Overflow {
+                monkey: 0,
+                item: 79
+            }),
+            calculate_worry_part1(&mut monkeys)
+        );
+
+        // Monkey 2 goes below zero after monkeys 0 and 1 have thrown, and the round is undone.
+        let input = TEST_INPUT2.replace("old * old", "old - 100");
+        let mut monkeys = parse_monkeys(&input).unwrap();
+        assert_eq!(
+            Err(MonkeyError::Overflow {
+                monkey: 2,
+                item: 79
+            }),
+            calculate_worry_part1(&mut monkeys)
+        );
+        assert_eq!(parse_monkeys(&input).unwrap(), monkeys);
+
+        // Tests whose least common multiple is bigger than a u128.
+        let input = TEST_INPUT2
+            .replace("by 23", "by 170141183460469231731687303715884105727")
+            .replace("by 19", "by 3");
+        let mut monkeys = parse_monkeys(&input).unwrap();
+        assert_eq!(
+            Err(MonkeyError::Modulus),
+            calculate_worry_part2(&mut monkeys)
+        );
+        assert_eq!(Ok(()), calculate_worry_part1(&mut monkeys));
+    }
+
+    #[test]
+    fn negative_worry_test() {
+        let input = "Monkey 0:
+  Starting items: 3
+  Operation: new = old - 10
+  Test: divisible by 7
+    If true: throw to monkey 1
+    If false: throw to monkey 0
+
+Monkey 1:
+  Starting items: 
+  Operation: new = old * 1
+  Test: divisible by 5
+    If true: throw to monkey 0
+    If false: throw to monkey 0";
+        let mut monkeys = parse_monkeys(input).unwrap();
+        assert_eq!(
+            Err(MonkeyError::Overflow { monkey: 0, item: 3 }),
+            calculate_worry_part1(&mut monkeys)
+        );
+        // Part 2 keeps -7 as its remainder modulo 35, which 7 still divides and 5 doesn't.
+        let mut monkeys = parse_monkeys(input).unwrap();
+        assert_eq!(Ok(()), calculate_worry_part2(&mut monkeys));
+        assert_eq!(vec![(-7i128).rem_euclid(35) as u128], monkeys[0].items);
+        assert_eq!(
+            vec![1, 1],
+            monkeys.iter().map(|m| m.inspected).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn large_modulus_test() {
+        // Ten monkeys testing primes from 101 to 149, so the modulus is bigger than a u64 and
+        // its square bigger than a u128.
+        let primes = [101, 103, 107, 109, 113, 127, 131, 137, 139, 149];
+        let input = primes
+            .iter()
+            .enumerate()
+            .map(|(i, prime)| {
+                format!(
+                    "Monkey {}:\n  Starting items: {}\n  Operation: new = old * old + {}\n  \
+                     Test: divisible by {}\n    If true: throw to monkey {}\n    \
+                     If false: throw to monkey {}",
+                    i,
+                    100 + i,
+                    i,
+                    prime,
+                    (i + 3) % 10,
+                    (i + 1) % 10
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let mut monkeys = parse_monkeys(&input).unwrap();
+        for _ in 0..1001 {
+            calculate_worry_part2(&mut monkeys).unwrap();
+        }
+        // Worked out separately with arbitrary precision.
+        assert_eq!(
+            vec![9987, 6523, 6538, 10004, 10002, 10003, 10007, 10008, 10009, 10010],
+            monkeys.iter().map(|m| m.inspected).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            [119518847571622066491, 600686236326637687765],
+            monkeys[0].items[..2]
+        );
+
+        let modulus = u128::MAX - 158;
+        let (_, expr) = parse_expression("old * old").unwrap();
+        assert_eq!(Some(1), expr.eval_mod(modulus - 1, modulus));
+        let (_, expr) = parse_expression("old * (old - 1) + old").unwrap();
+        assert_eq!(Some(4), expr.eval_mod(modulus - 2, modulus));
+        let (_, expr) = parse_expression("old - (old + 1)").unwrap();
+        assert_eq!(Some(modulus - 1), expr.eval_mod(5, modulus));
+    }
+
+    #[test]
+    fn calculate_worry_test() {
+        let mut monkeys = parse_monkeys(TEST_INPUT2).unwrap();
+
+        calculate_worry_part1(&mut monkeys).unwrap();
+        calculate_worry_part1(&mut monkeys).unwrap();
+        assert_eq!(0, monkeys[2].items.len());
+        assert_eq!(0, monkeys[3].items.len());
+
+        dbg!(monkeys[0].items.clone());
+        assert!(monkeys[0].items.contains(&695u128));
+        assert!(monkeys[0].items.contains(&10u128));
+    }
     #[test]
     fn calculate_monkey_business_twenty_test() {
-        let (_, mut monkeys) = parse_monkeys(TEST_INPUT2).unwrap();
+        let mut monkeys = parse_monkeys(TEST_INPUT2).unwrap();
 
         for _ in 0..20 {
-            calculate_worry_part1(&mut monkeys);
+            calculate_worry_part1(&mut monkeys).unwrap();
         }
         let mut ordered_inspections = monkeys.iter().map(|m| m.inspected).collect::<Vec<u64>>();
         ordered_inspections.sort_by(|a, b| b.cmp(a));
@@ -107,10 +323,10 @@ Monkey 3:
 
     #[test]
     fn calculate_monkey_business_part2_test() {
-        let (_, mut monkeys) = parse_monkeys(TEST_INPUT2).unwrap();
+        let mut monkeys = parse_monkeys(TEST_INPUT2).unwrap();
 
         for _ in 0..10000 {
-            calculate_worry_part2(&mut monkeys);
+            calculate_worry_part2(&mut monkeys).unwrap();
         }
         let mut ordered_inspections = monkeys.iter().map(|m| m.inspected).collect::<Vec<u64>>();
         dbg!(ordered_inspections.clone());
@@ -120,126 +336,330 @@ Monkey 3:
     }
 }
 
-fn add(old: u128, argument: u128) -> u128 {
-    old + argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// The right hand side of `new = ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Old,
+    Constant(u128),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn binary(op: char, lhs: Expr, rhs: Expr) -> Self {
+        let op = match op {
+            '+' => Op::Add,
+            '-' => Op::Sub,
+            '*' => Op::Mul,
+            '/' => Op::Div,
+            _ => unreachable!(),
+        };
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    /// `None` if the worry level goes below zero, overflows or is divided by zero.
+    fn eval(&self, old: u128) -> Option<u128> {
+        match self {
+            Expr::Old => Some(old),
+            Expr::Constant(c) => Some(*c),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(old)?, rhs.eval(old)?);
+                match op {
+                    Op::Add => lhs.checked_add(rhs),
+                    Op::Sub => lhs.checked_sub(rhs),
+                    Op::Mul => lhs.checked_mul(rhs),
+                    Op::Div => lhs.checked_div(rhs),
+                }
+            }
+        }
+    }
+
+    /// Evaluates modulo `modulus`, which keeps the remainder by anything dividing it. Division
+    /// doesn't, so that gives `None`. There is no telling whether the worry level went below
+    /// zero, so a subtraction wraps around the modulus like the rest of the arithmetic does.
+    fn eval_mod(&self, old: u128, modulus: u128) -> Option<u128> {
+        match self {
+            Expr::Old => Some(old % modulus),
+            Expr::Constant(c) => Some(c % modulus),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval_mod(old, modulus)?, rhs.eval_mod(old, modulus)?);
+                match op {
+                    Op::Add => Some(add_mod(lhs, rhs, modulus)),
+                    Op::Sub => Some(add_mod(lhs, modulus - rhs, modulus)),
+                    Op::Mul => Some(mul_mod(lhs, rhs, modulus)),
+                    Op::Div => None,
+                }
+            }
+        }
+    }
+
+    fn has_division(&self) -> bool {
+        match self {
+            Expr::Binary(op, lhs, rhs) => {
+                *op == Op::Div || lhs.has_division() || rhs.has_division()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `a + b` modulo `modulus`, for `a` and `b` already below it.
+fn add_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    if a >= modulus - b {
+        a - (modulus - b)
+    } else {
+        a + b
+    }
+}
+
+/// `a * b` modulo `modulus`, for `a` and `b` already below it, by doubling when the product
+/// doesn't fit in a u128.
+fn mul_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    if let Some(product) = a.checked_mul(b) {
+        return product % modulus;
+    }
+    let mut product = 0;
+    for bit in (0..u128::BITS - b.leading_zeros()).rev() {
+        product = add_mod(product, product, modulus);
+        if b & (1 << bit) != 0 {
+            product = add_mod(product, a, modulus);
+        }
+    }
+    product
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MonkeyError {
+    /// `monkey` is the id in the header, or the monkey's position if that can't be read, and
+    /// `line` is the line that couldn't be parsed, or the header if the id isn't the monkey's
+    /// position.
+    Parse { monkey: usize, line: String },
+    /// Worry levels are kept modulo the monkeys' tests, which division doesn't work with.
+    Division { monkey: usize },
+    /// The worry level went below zero or got too big to hold. Only part 1 can tell, as part 2
+    /// keeps worry levels modulo the tests.
+    Overflow { monkey: usize, item: u128 },
+    /// The least common multiple of the tests is too big to hold.
+    Modulus,
+    /// A test can't divide by zero.
+    ZeroDivisor { monkey: usize },
+    /// Throwing to a monkey that isn't there.
+    Target { monkey: usize, target: usize },
 }
-fn sub(old: u128, argument: u128) -> u128 {
-    old - argument
+
+fn parse_factor(input: &str) -> nom::IResult<&str, Expr> {
+    use nom::branch::alt;
+    use nom::bytes::complete::tag;
+    use nom::character::complete::{char, space0, u128};
+    use nom::combinator::{map, value};
+    use nom::sequence::delimited;
+
+    delimited(
+        space0,
+        alt((
+            value(Expr::Old, tag("old")),
+            map(u128, Expr::Constant),
+            delimited(char('('), parse_expression, char(')')),
+        )),
+        space0,
+    )(input)
 }
-fn mult(old: u128, argument: u128) -> u128 {
-    old * argument
+
+fn parse_term(input: &str) -> nom::IResult<&str, Expr> {
+    use nom::character::complete::one_of;
+    use nom::multi::fold_many0;
+    use nom::sequence::pair;
+
+    let (input, first) = parse_factor(input)?;
+    fold_many0(
+        pair(one_of("*/"), parse_factor),
+        move || first.clone(),
+        |lhs, (op, rhs)| Expr::binary(op, lhs, rhs),
+    )(input)
 }
-fn div(old: u128, argument: u128) -> u128 {
-    old / argument
+
+/// Arithmetic over `old` and constants, with the usual precedence and parentheses.
+fn parse_expression(input: &str) -> nom::IResult<&str, Expr> {
+    use nom::character::complete::one_of;
+    use nom::multi::fold_many0;
+    use nom::sequence::pair;
+
+    let (input, first) = parse_term(input)?;
+    fold_many0(
+        pair(one_of("+-"), parse_term),
+        move || first.clone(),
+        |lhs, (op, rhs)| Expr::binary(op, lhs, rhs),
+    )(input)
 }
 
 fn parse_monkey(input: &str) -> nom::IResult<&str, Monkey> {
     use nom::bytes::complete::tag;
-    use nom::character::complete::alphanumeric0;
     use nom::character::complete::char;
-    use nom::character::complete::digit0;
     use nom::character::complete::newline;
-    use nom::character::complete::one_of;
+    use nom::character::complete::{digit1, u128};
+    use nom::combinator::map_res;
     use nom::multi::separated_list0;
     use nom::sequence::preceded;
-    use nom::sequence::tuple;
 
-    let (input, id) = preceded(tag("Monkey "), digit0)(input)?;
+    let monkey = || map_res(digit1, str::parse::<usize>);
+    let (input, id) = preceded(tag("Monkey "), monkey())(input)?;
     let (input, _) = preceded(char(':'), newline)(input)?;
-    let (input, starting_items) = preceded(
-        tag("  Starting items: "),
-        separated_list0(tag(", "), digit0),
-    )(input)?;
+    let (input, items) =
+        preceded(tag("  Starting items: "), separated_list0(tag(", "), u128))(input)?;
     let (input, _) = newline(input)?;
-    let (input, operation) = preceded(
-        tag("  Operation: new = old "),
-        tuple((one_of("+-*/"), preceded(char(' '), alphanumeric0))),
-    )(input)?;
+    let (input, operation) = preceded(tag("  Operation: new ="), parse_expression)(input)?;
     let (input, _) = newline(input)?;
-    let (input, thetest) = preceded(tag("  Test: divisible by "), digit0)(input)?;
+    let (input, divisible_by) = preceded(tag("  Test: divisible by "), u128)(input)?;
     let (input, _) = newline(input)?;
-    let (input, iftrue) = preceded(tag("    If true: throw to monkey "), digit0)(input)?;
+    let (input, iftrue) = preceded(tag("    If true: throw to monkey "), monkey())(input)?;
     let (input, _) = newline(input)?;
-    let (input, iffalse) = preceded(tag("    If false: throw to monkey "), digit0)(input)?;
-
-    let worry_calculation = match operation.0 {
-        '+' => add,
-        '-' => sub,
-        '*' => mult,
-        '/' => div,
-        _ => panic!(),
-    };
-
-    let worry_argument = match operation.1 {
-        "old" => WorryArgument::Old,
-        _ => WorryArgument::Constant(operation.1.parse::<u128>().unwrap()),
-    };
+    let (input, iffalse) = preceded(tag("    If false: throw to monkey "), monkey())(input)?;
 
     Ok((
         input,
         Monkey {
-            id: id.parse::<usize>().unwrap(),
-            divisible_by: thetest.parse::<u128>().unwrap(),
-            iftrue: iftrue.parse::<usize>().unwrap(),
-            iffalse: iffalse.parse::<usize>().unwrap(),
-            worry_calculation,
-            worry_argument,
-            items: starting_items
-                .iter()
-                .map(|si| si.parse::<u128>().unwrap())
-                .collect::<Vec<u128>>(),
+            id,
+            divisible_by,
+            iftrue,
+            iffalse,
+            operation,
+            items,
             inspected: 0,
         },
     ))
 }
 
-fn parse_monkeys(input: &str) -> nom::IResult<&str, Vec<Monkey>> {
-    use nom::character::complete::newline;
-    use nom::multi::count;
-    use nom::multi::separated_list0;
-
-    let (input, monkeys) = separated_list0(count(newline, 2), parse_monkey)(input)?;
-    Ok((input, monkeys))
+/// Every monkey, with blocks of nothing but white space skipped.
+fn parse_monkeys(input: &str) -> Result<Vec<Monkey>, MonkeyError> {
+    let monkeys = input
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .enumerate()
+        .map(|(i, block)| {
+            let id = block
+                .strip_prefix("Monkey ")
+                .and_then(|rest| rest.split(':').next())
+                .and_then(|id| id.parse().ok())
+                .unwrap_or(i);
+            // The line holding the first thing that couldn't be parsed.
+            let error_at = |rest: &str| {
+                let offset = block.len() - rest.len();
+                let start = block[..offset].rfind('\n').map_or(0, |n| n + 1);
+                let end = block[offset..]
+                    .find('\n')
+                    .map_or(block.len(), |n| n + offset);
+                MonkeyError::Parse {
+                    monkey: id,
+                    line: block[start..end].to_string(),
+                }
+            };
+            let (rest, monkey) = parse_monkey(block).map_err(|e| match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => error_at(e.input),
+                nom::Err::Incomplete(_) => error_at(""),
+            })?;
+            if !rest.trim().is_empty() {
+                return Err(error_at(rest.trim_start()));
+            }
+            // Targets are positions, so the ids have to count up from 0.
+            if monkey.id != i {
+                return Err(error_at(block.trim_start()));
+            }
+            if monkey.operation.has_division() {
+                return Err(MonkeyError::Division { monkey: id });
+            }
+            if monkey.divisible_by == 0 {
+                return Err(MonkeyError::ZeroDivisor { monkey: id });
+            }
+            Ok(monkey)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for monkey in monkeys.iter() {
+        for target in [monkey.iftrue, monkey.iffalse] {
+            if target >= monkeys.len() {
+                return Err(MonkeyError::Target {
+                    monkey: monkey.id,
+                    target,
+                });
+            }
+        }
+    }
+    Ok(monkeys)
 }
 
-fn calculate_worry_common(monkies: &mut Vec<Monkey>, reduce_worry_after_inspection: bool) {
-    let mut common_denominator = monkies[0].divisible_by;
-    for i in 1..monkies.len() {
-        common_denominator *= monkies[i].divisible_by;
-    }
+/// Plays one round. On an error the monkeys are left as they were before it.
+fn calculate_worry_common(
+    monkies: &mut [Monkey],
+    reduce_worry_after_inspection: bool,
+) -> Result<(), MonkeyError> {
+    // Only needed once worry levels stop being divided down.
+    let common_denominator = if reduce_worry_after_inspection {
+        None
+    } else {
+        let lcm = monkies.iter().try_fold(1u128, |lcm, m| {
+            (lcm / gcd(lcm, m.divisible_by)).checked_mul(m.divisible_by)
+        });
+        Some(lcm.ok_or(MonkeyError::Modulus)?)
+    };
+    // Items move between monkeys during the round, so keep them to put back on an error.
+    let before = monkies
+        .iter()
+        .map(|m| (m.items.clone(), m.inspected))
+        .collect::<Vec<_>>();
     for i in 0..monkies.len() {
         let mut items = Vec::<u128>::new();
         std::mem::swap(&mut monkies[i].items, &mut items);
         monkies[i].inspected += items.len() as u64;
         for item in items {
-            let calc = monkies[i].worry_calculation;
-            let new_worry = match monkies[i].worry_argument {
-                WorryArgument::Old => calc(item, item),
-                WorryArgument::Constant(c) => calc(item, c),
+            let operation = &monkies[i].operation;
+            let new_worry = match common_denominator {
+                None => operation.eval(item).map(|worry| worry / 3),
+                Some(modulus) => operation.eval_mod(item, modulus),
+            };
+            let Some(new_worry) = new_worry else {
+                let monkey = monkies[i].id;
+                for (m, (items, inspected)) in monkies.iter_mut().zip(before) {
+                    m.items = items;
+                    m.inspected = inspected;
+                }
+                return Err(MonkeyError::Overflow { monkey, item });
             };
 
             let iftrue = monkies[i].iftrue;
             let iffalse = monkies[i].iffalse;
 
-            let new_worry_after = if reduce_worry_after_inspection {
-                (new_worry / 3) % common_denominator
-            } else {
-                new_worry % common_denominator
-            };
-            if new_worry_after % monkies[i].divisible_by == 0 {
-                monkies[iftrue].items.push(new_worry_after);
+            if new_worry % monkies[i].divisible_by == 0 {
+                monkies[iftrue].items.push(new_worry);
             } else {
-                monkies[iffalse].items.push(new_worry_after);
+                monkies[iffalse].items.push(new_worry);
             }
         }
     }
+    Ok(())
 }
 
-fn calculate_worry_part1(monkies: &mut Vec<Monkey>) {
-    calculate_worry_common(monkies, true);
+fn calculate_worry_part1(monkies: &mut [Monkey]) -> Result<(), MonkeyError> {
+    calculate_worry_common(monkies, true)
 }
-fn calculate_worry_part2(monkies: &mut Vec<Monkey>) {
-    calculate_worry_common(monkies, false);
+/// Worry levels are kept modulo the least common multiple of the tests, which doesn't change
+/// where any item is thrown. A worry level that goes below zero is kept as its remainder too, so
+/// it is thrown where the negative number would be rather than giving `Overflow` like part 1.
+fn calculate_worry_part2(monkies: &mut [Monkey]) -> Result<(), MonkeyError> {
+    calculate_worry_common(monkies, false)
 }
 
 fn main() {
@@ -248,23 +668,23 @@ fn main() {
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path).unwrap();
+    let mut file = File::open(path).unwrap();
     let mut input = String::new();
     file.read_to_string(&mut input).unwrap();
 
-    let (_, mut monkeys) = parse_monkeys(&input).unwrap();
+    let mut monkeys = parse_monkeys(&input).unwrap();
 
     for _ in 0..20 {
-        calculate_worry_part1(&mut monkeys);
+        calculate_worry_part1(&mut monkeys).unwrap();
     }
     let mut ordered_inspections = monkeys.iter().map(|m| m.inspected).collect::<Vec<u64>>();
     ordered_inspections.sort_by(|a, b| b.cmp(a));
     println!("{}", ordered_inspections[0] * ordered_inspections[1]);
 
-    let (_, mut monkeys) = parse_monkeys(&input).unwrap();
+    let mut monkeys = parse_monkeys(&input).unwrap();
 
     for _ in 0..10000 {
-        calculate_worry_part2(&mut monkeys);
+        calculate_worry_part2(&mut monkeys).unwrap();
     }
     let mut ordered_inspections = monkeys.iter().map(|m| m.inspected).collect::<Vec<u64>>();
     ordered_inspections.sort_by(|a, b| b.cmp(a));
@@ -1,13 +1,22 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::io::BufRead;
+use std::sync::OnceLock;
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn get_number(line: &str) -> Result<u64, CalibrationError> {
+        value_of(english_vocabulary().first_and_last(line))
+    }
+
     #[test]
     fn test_parse_line() {
-        assert_eq!(get_number_simple("1abc2"), 12);
-        assert_eq!(get_number_simple("pqr3stu8vwx"), 38);
-        assert_eq!(get_number_simple("a1b2c3d4e5f"), 15);
-        assert_eq!(get_number_simple("treb7uchet"), 77);
+        assert_eq!(get_number_simple("1abc2"), Ok(12));
+        assert_eq!(get_number_simple("pqr3stu8vwx"), Ok(38));
+        assert_eq!(get_number_simple("a1b2c3d4e5f"), Ok(15));
+        assert_eq!(get_number_simple("treb7uchet"), Ok(77));
     }
 
     #[test]
@@ -18,20 +27,20 @@ a1b2c3d4e5f
 treb7uchet";
         let mut answer = 0;
         for line in INPUT.lines() {
-            answer += get_number_simple(line);
+            answer += get_number_simple(line).unwrap();
         }
         assert_eq!(answer, 142);
     }
 
     #[test]
     fn test_parse_line2() {
-        assert_eq!(get_number("two1nine"), 29);
-        assert_eq!(get_number("eightwothree"), 83);
-        assert_eq!(get_number("abcone2threexyz"), 13);
-        assert_eq!(get_number("xtwone3four"), 24);
-        assert_eq!(get_number("4nineeightseven2"), 42);
-        assert_eq!(get_number("zoneight234"), 14);
-        assert_eq!(get_number("7pqrstsixteen"), 76);
+        assert_eq!(get_number("two1nine"), Ok(29));
+        assert_eq!(get_number("eightwothree"), Ok(83));
+        assert_eq!(get_number("abcone2threexyz"), Ok(13));
+        assert_eq!(get_number("xtwone3four"), Ok(24));
+        assert_eq!(get_number("4nineeightseven2"), Ok(42));
+        assert_eq!(get_number("zoneight234"), Ok(14));
+        assert_eq!(get_number("7pqrstsixteen"), Ok(76));
     }
 
     #[test]
@@ -45,72 +54,328 @@ zoneight234
 7pqrstsixteen";
         let mut answer = 0;
         for line in INPUT.lines() {
-            answer += get_number(line);
+            answer += get_number(line).unwrap();
         }
         assert_eq!(answer, 281);
     }
-}
 
-fn match_digit(number: &str) -> char {
-    match number {
-        "0" => '0',
-        "1" => '1',
-        "2" => '2',
-        "3" => '3',
-        "4" => '4',
-        "5" => '5',
-        "6" => '6',
-        "7" => '7',
-        "8" => '8',
-        "9" => '9',
-        "one" => '1',
-        "two" => '2',
-        "three" => '3',
-        "four" => '4',
-        "five" => '5',
-        "six" => '6',
-        "seven" => '7',
-        "eight" => '8',
-        "nine" => '9',
-        _ => panic!(),
+    #[test]
+    fn test_overlapping_words() {
+        assert_eq!(get_number("eightwo"), Ok(82));
+        assert_eq!(get_number("oneight"), Ok(18));
+        assert_eq!(get_number("twone"), Ok(21));
+        assert_eq!(get_number("sevenine"), Ok(79));
+    }
+
+    #[test]
+    fn test_no_digit() {
+        assert_eq!(get_number_simple("abc"), Err(CalibrationError::NoDigit));
+        assert_eq!(get_number_simple(""), Err(CalibrationError::NoDigit));
+        assert_eq!(get_number("zero"), Err(CalibrationError::NoDigit));
+    }
+
+    #[test]
+    fn test_find_iter() {
+        let vocabulary = Vocabulary::english();
+        let matches = vocabulary.find_iter("xtwone3").collect::<Vec<_>>();
+        assert_eq!(3, matches.len());
+        assert_eq!(
+            (1, "two", 2),
+            (matches[0].start, matches[0].token, matches[0].value)
+        );
+        assert_eq!(
+            (3, "one", 1),
+            (matches[1].start, matches[1].token, matches[1].value)
+        );
+        assert_eq!(
+            (6, "3", 3),
+            (matches[2].start, matches[2].token, matches[2].value)
+        );
+    }
+
+    #[test]
+    fn test_custom_vocabulary() {
+        let vocabulary = Vocabulary::english().with_tokens([("zero", 0)]);
+        assert_eq!(value_of(vocabulary.first_and_last("zero5")), Ok(5));
+        assert_eq!(value_of(vocabulary.first_and_last("fivezero")), Ok(50));
+
+        let german = Vocabulary::digits().with_tokens([
+            ("eins", 1),
+            ("zwei", 2),
+            ("drei", 3),
+            ("vier", 4),
+            ("fünf", 5),
+            ("sechs", 6),
+            ("sieben", 7),
+            ("acht", 8),
+            ("neun", 9),
+        ]);
+        assert_eq!(value_of(german.first_and_last("xfünfzweinsx")), Ok(51));
+        assert_eq!(value_of(german.first_and_last("achtzehn")), Ok(88));
+        assert_eq!(
+            value_of(german.first_and_last("eight")),
+            Err(CalibrationError::NoDigit)
+        );
+
+        let numbers = Vocabulary::english().with_tokens([("ten", 10), ("hundred", 100)]);
+        assert_eq!(value_of(numbers.first_and_last("ten5")), Ok(105));
+        assert_eq!(value_of(numbers.first_and_last("5ten")), Ok(510));
+        assert_eq!(value_of(numbers.first_and_last("tenxhundred")), Ok(10100));
+        assert_eq!(value_of(numbers.first_and_last("0ten0")), Ok(0));
+        assert_eq!(value_of(numbers.first_and_last("ten")), Ok(1010));
+        let huge = Vocabulary::digits().with_tokens([("huge", u64::MAX)]);
+        assert_eq!(
+            value_of(huge.first_and_last("huge1")),
+            Err(CalibrationError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_process_calibration() {
+        const INPUT: &str = "two1nine
+eightwothree
+abcone2threexyz
+xtwone3four
+4nineeightseven2
+zoneight234
+7pqrstsixteen";
+        let mut reports = Vec::new();
+        let mut collect = |r: &LineReport| reports.push((r.to_text(), r.to_json()));
+        let totals = process_calibration(INPUT.as_bytes(), Some(&mut collect)).unwrap();
+        assert_eq!(7, totals.lines);
+        assert_eq!(281, totals.part2);
+        assert_eq!(11 + 22 + 33 + 42 + 24 + 77, totals.part1);
+        assert_eq!(6, totals.disagreements);
+
+        assert_eq!(
+            "line 2: simple - words 83 first \"eight\"@0..5 last \"three\"@7..12 DISAGREE",
+            reports[1].0
+        );
+        assert_eq!(
+            "line 5: simple 42 words 42 first \"4\"@0..1 last \"2\"@15..16",
+            reports[4].0
+        );
+        assert_eq!(
+            "{\"line\":2,\"simple\":null,\"words\":83,\"first\":{\"token\":\"eight\",\"value\":8,\"start\":0,\"end\":5},\"last\":{\"token\":\"three\",\"value\":3,\"start\":7,\"end\":12},\"disagrees\":true}",
+            reports[1].1
+        );
+
+        let totals = process_calibration("1abc2\n\ntreb7uchet".as_bytes(), None).unwrap();
+        assert_eq!(3, totals.lines);
+        assert_eq!(12 + 77, totals.part1);
+        assert_eq!(12 + 77, totals.part2);
+        assert_eq!(0, totals.disagreements);
+        assert_eq!(1, totals.missing_part1);
+        assert_eq!(1, totals.missing_part2);
+
+        let totals = process_calibration("one\nabc\n7".as_bytes(), None).unwrap();
+        assert_eq!(77, totals.part1);
+        assert_eq!(11 + 77, totals.part2);
+        assert_eq!(2, totals.missing_part1);
+        assert_eq!(1, totals.missing_part2);
     }
+
+    #[test]
+    fn test_report_json_escapes() {
+        let report = LineReport {
+            line: 1,
+            simple: None,
+            words: Some(11),
+            first: Some(TokenMatch {
+                token: "a\u{1}\n\"\\",
+                value: 1,
+                start: 0,
+                end: 5,
+            }),
+            last: None,
+        };
+        let json = serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap();
+        assert_eq!("a\u{1}\n\"\\", json["first"]["token"]);
+        assert_eq!(serde_json::Value::Null, json["last"]);
+        assert_eq!(true, json["disagrees"]);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum CalibrationError {
+    NoDigit,
+    /// The value doesn't fit in a `u64`.
+    Overflow,
+    Io(std::io::ErrorKind),
+}
+
+/// A token found in a line, `start` and `end` are byte offsets.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
+struct TokenMatch<'a> {
+    token: &'a str,
+    value: u64,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: HashMap<u8, usize>,
+    fail: usize,
+    /// Index of the token ending at this node.
+    token: Option<usize>,
+    /// Nearest node along the fail links that ends a token.
+    output: Option<usize>,
 }
 
-fn get_number(line: &str) -> u64 {
-    const NUMBERS: [&str; 19] = [
-        "1", "2", "3", "4", "5", "6", "7", "8", "9", "0", "one", "two", "three", "four", "five",
-        "six", "seven", "eight", "nine",
-    ];
+/// Tokens mapped to values, matched with an Aho-Corasick automaton so every token in a line,
+/// including overlapping ones like "eightwo", is found in a single pass.
+#[derive(Debug, Clone)]
+struct Vocabulary {
+    tokens: Vec<(String, u64)>,
+    nodes: Vec<Node>,
+}
 
-    let test: [Vec<(usize, &str)>; 19] =
-        NUMBERS.map(|search_for| line.match_indices(search_for).collect());
+impl Vocabulary {
+    fn new<S: AsRef<str>>(tokens: impl IntoIterator<Item = (S, u64)>) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|(t, v)| (t.as_ref().to_string(), v))
+            .filter(|(t, _)| !t.is_empty())
+            .collect::<Vec<_>>();
 
-    let mut number_map = Vec::new();
-    for i in test {
-        for j in i {
-            number_map.push(j);
+        let mut nodes = vec![Node::default()];
+        for (index, (token, _)) in tokens.iter().enumerate() {
+            let mut current = 0;
+            for b in token.bytes() {
+                current = match nodes[current].children.get(&b) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(Node::default());
+                        let next = nodes.len() - 1;
+                        nodes[current].children.insert(b, next);
+                        next
+                    }
+                };
+            }
+            nodes[current].token = Some(index);
+        }
+
+        // Breadth first so every node's fail link is set before its children's.
+        let mut queue = VecDeque::from_iter(nodes[0].children.values().copied());
+        while let Some(current) = queue.pop_front() {
+            let children = nodes[current]
+                .children
+                .iter()
+                .map(|(b, n)| (*b, *n))
+                .collect::<Vec<_>>();
+            for (b, child) in children {
+                let mut fail = nodes[current].fail;
+                let fail = loop {
+                    if let Some(&next) = nodes[fail].children.get(&b) {
+                        break next;
+                    }
+                    if fail == 0 {
+                        break 0;
+                    }
+                    fail = nodes[fail].fail;
+                };
+                nodes[child].fail = fail;
+                nodes[child].output = if nodes[fail].token.is_some() {
+                    Some(fail)
+                } else {
+                    nodes[fail].output
+                };
+                queue.push_back(child);
+            }
         }
+
+        Self { tokens, nodes }
     }
 
-    number_map.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-    let first = number_map.first().unwrap();
-    let last = number_map.last().unwrap();
+    /// The digits 0 to 9.
+    fn digits() -> Self {
+        Self::new((0..10).map(|d| (d.to_string(), d)))
+    }
 
-    let f = match_digit(first.1);
-    let l = match_digit(last.1);
+    /// The digits plus the words one to nine, as used by part 2.
+    fn english() -> Self {
+        const WORDS: [&str; 9] = [
+            "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+        ];
+        Self::digits().with_tokens(WORDS.iter().zip(1..))
+    }
 
-    let mut num_digits = String::new();
-    num_digits.push(f);
-    num_digits.push(l);
+    fn with_tokens<S: AsRef<str>>(self, tokens: impl IntoIterator<Item = (S, u64)>) -> Self {
+        let more = tokens
+            .into_iter()
+            .map(|(t, v)| (t.as_ref().to_string(), v))
+            .collect::<Vec<_>>();
+        Self::new(self.tokens.into_iter().chain(more))
+    }
 
-    num_digits.parse().unwrap()
+    /// Every token in the line, ordered by where they end.
+    fn find_iter<'a>(&'a self, line: &'a str) -> impl Iterator<Item = TokenMatch<'a>> + 'a {
+        let mut state = 0;
+        line.bytes().enumerate().flat_map(move |(i, b)| {
+            state = loop {
+                if let Some(&next) = self.nodes[state].children.get(&b) {
+                    break next;
+                }
+                if state == 0 {
+                    break 0;
+                }
+                state = self.nodes[state].fail;
+            };
+            let first = if self.nodes[state].token.is_some() {
+                Some(state)
+            } else {
+                self.nodes[state].output
+            };
+            std::iter::successors(first, |n| self.nodes[*n].output).map(move |n| {
+                let (token, value) = &self.tokens[self.nodes[n].token.unwrap()];
+                TokenMatch {
+                    start: i + 1 - token.len(),
+                    end: i + 1,
+                    token,
+                    value: *value,
+                }
+            })
+        })
+    }
+
+    /// The first and last tokens by starting position.
+    fn first_and_last<'a>(&'a self, line: &'a str) -> Option<(TokenMatch<'a>, TokenMatch<'a>)> {
+        self.find_iter(line).fold(None, |found, m| match found {
+            None => Some((m, m)),
+            Some((first, last)) => Some((
+                if m.start < first.start { m } else { first },
+                if m.start > last.start { m } else { last },
+            )),
+        })
+    }
 }
 
-fn get_number_simple(line: &str) -> u64 {
+/// The first and last values written one after the other, so tokens worth 10 or more keep all
+/// their digits.
+fn value_of(found: Option<(TokenMatch, TokenMatch)>) -> Result<u64, CalibrationError> {
+    let (first, last) = found.ok_or(CalibrationError::NoDigit)?;
+    concatenate(first.value, last.value)
+}
+
+fn concatenate(first: u64, last: u64) -> Result<u64, CalibrationError> {
+    let shift = 10u64.pow(last.checked_ilog10().unwrap_or(0) + 1);
+    first
+        .checked_mul(shift)
+        .and_then(|f| f.checked_add(last))
+        .ok_or(CalibrationError::Overflow)
+}
+
+fn english_vocabulary() -> &'static Vocabulary {
+    static ENGLISH: OnceLock<Vocabulary> = OnceLock::new();
+    ENGLISH.get_or_init(Vocabulary::english)
+}
+
+fn get_number_simple(line: &str) -> Result<u64, CalibrationError> {
     let mut first: Option<char> = None;
     let mut last: Option<char> = None;
     for c in line.chars() {
-        match (c.is_digit(10), first.is_some(), last.is_some()) {
+        match (c.is_ascii_digit(), first.is_some(), last.is_some()) {
             (true, false, false) => {
                 first = Some(c);
                 last = Some(c);
@@ -123,31 +388,140 @@ fn get_number_simple(line: &str) -> u64 {
         let mut s = String::new();
         s.push(f);
         s.push(l);
-        return s.parse().unwrap();
+        Ok(s.parse().unwrap())
     } else {
-        panic!();
+        Err(CalibrationError::NoDigit)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+struct CalibrationTotals {
+    lines: usize,
+    part1: u64,
+    part2: u64,
+    /// Lines where the digit only and word aware values differ.
+    disagreements: usize,
+    /// Lines with no value for each part, which add nothing to its total.
+    missing_part1: usize,
+    missing_part2: usize,
+}
+
+/// What was found on a single line, `line` is numbered from 1. The first and last matches
+/// are from the word aware interpretation.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+struct LineReport<'a> {
+    line: usize,
+    simple: Option<u64>,
+    words: Option<u64>,
+    first: Option<TokenMatch<'a>>,
+    last: Option<TokenMatch<'a>>,
+}
+
+impl LineReport<'_> {
+    fn disagrees(&self) -> bool {
+        self.simple != self.words
+    }
+
+    fn to_text(&self) -> String {
+        let value = |v: Option<u64>| v.map_or("-".to_string(), |v| v.to_string());
+        let found = |m: Option<TokenMatch>| {
+            m.map_or("-".to_string(), |m| {
+                format!("{:?}@{}..{}", m.token, m.start, m.end)
+            })
+        };
+        format!(
+            "line {}: simple {} words {} first {} last {}{}",
+            self.line,
+            value(self.simple),
+            value(self.words),
+            found(self.first),
+            found(self.last),
+            if self.disagrees() { " DISAGREE" } else { "" }
+        )
+    }
+
+    /// A single line JSON object.
+    fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Json<'a, 'b> {
+            #[serde(flatten)]
+            report: &'b LineReport<'a>,
+            disagrees: bool,
+        }
+        serde_json::to_string(&Json {
+            report: self,
+            disagrees: self.disagrees(),
+        })
+        .unwrap()
     }
 }
 
+/// Work out both parts in a single pass over the reader, scanning each line once for each
+/// interpretation. Lines without a value for one of them add nothing to its total and are
+/// counted as missing. When `on_line` is given it is called with the details of every line.
+fn process_calibration(
+    reader: impl BufRead,
+    mut on_line: Option<&mut dyn FnMut(&LineReport)>,
+) -> Result<CalibrationTotals, CalibrationError> {
+    let mut totals = CalibrationTotals::default();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| CalibrationError::Io(e.kind()))?;
+        let simple = get_number_simple(&line).ok();
+        let found = english_vocabulary().first_and_last(&line);
+        let words = value_of(found).ok();
+
+        totals.lines += 1;
+        totals.part1 += simple.unwrap_or(0);
+        totals.part2 += words.unwrap_or(0);
+        totals.missing_part1 += usize::from(simple.is_none());
+        totals.missing_part2 += usize::from(words.is_none());
+        if simple != words {
+            totals.disagreements += 1;
+        }
+
+        if let Some(on_line) = on_line.as_mut() {
+            on_line(&LineReport {
+                line: i + 1,
+                simple,
+                words,
+                first: found.map(|f| f.0),
+                last: found.map(|f| f.1),
+            });
+        }
+    }
+    Ok(totals)
+}
+
 fn main() {
     use std::fs::File;
-    use std::io::Read;
+    use std::io::BufReader;
     use std::path::Path;
 
     let path = Path::new("input.txt");
-    let mut file = File::open(&path).unwrap();
-    let mut input = String::new();
-    file.read_to_string(&mut input).unwrap();
+    let file = File::open(path).unwrap();
 
-    let mut answer = 0;
-    for line in input.lines() {
-        answer += get_number_simple(line);
-    }
+    // `verbose` or `json` prints the details of every line as well.
+    let mode = std::env::args().nth(1);
+    let mut print_line = |report: &LineReport| match mode.as_deref() {
+        Some("json") => println!("{}", report.to_json()),
+        _ => println!("{}", report.to_text()),
+    };
+    let on_line: Option<&mut dyn FnMut(&LineReport)> = match mode.as_deref() {
+        Some("verbose" | "json") => Some(&mut print_line),
+        _ => None,
+    };
+    let totals = process_calibration(BufReader::new(file), on_line).unwrap();
 
-    println!("The part 1 answer is {0}", answer);
-    let mut answer = 0;
-    for line in input.lines() {
-        answer += get_number(line);
+    println!("The part 1 answer is {0}", totals.part1);
+    println!("The part 2 answer is {0}", totals.part2);
+    println!(
+        "{0} of {1} lines differ between the two parts",
+        totals.disagreements, totals.lines
+    );
+    if totals.missing_part1 + totals.missing_part2 > 0 {
+        println!(
+            "{0} lines have no part 1 value and {1} no part 2 value",
+            totals.missing_part1, totals.missing_part2
+        );
     }
-    println!("The part 2 answer is {0}", answer);
 }